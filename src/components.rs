@@ -1 +1,4 @@
-pub use crate::mq::components::{MeshColor, Projection, SimpleMesh, SimpleMeshTexture};
+pub use crate::mq::components::{
+    BlendMode, DynamicMesh, MeshColor, Projection, ScalingMode, SimpleMesh, SimpleMeshTexture,
+};
+pub use crate::mq::{AnimatedSprite, Sprite};
@@ -0,0 +1,394 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{File, OpenOptions},
+    io::{ErrorKind, Read},
+    mem::size_of,
+    os::unix::{fs::OpenOptionsExt, io::AsRawFd},
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+use super::{GamepadAxis, GamepadBackend, GamepadButton, GamepadId, GamepadInput};
+
+const INPUT_DIR: &str = "/dev/input";
+
+/// Events read from a device at once
+const READ_EVENTS: usize = 64;
+
+const EV_KEY: u16 = 0x01;
+const EV_ABS: u16 = 0x03;
+
+const BTN_GAMEPAD: u16 = 0x130;
+const KEY_MAX: usize = 0x2ff;
+
+const ABS_X: u16 = 0x00;
+const ABS_Y: u16 = 0x01;
+const ABS_Z: u16 = 0x02;
+const ABS_RX: u16 = 0x03;
+const ABS_RY: u16 = 0x04;
+const ABS_RZ: u16 = 0x05;
+const ABS_HAT0X: u16 = 0x10;
+const ABS_HAT0Y: u16 = 0x11;
+
+/// Reads the gamepads of `/dev/input/event*`, devices the user may not read are skipped
+pub struct EvdevBackend {
+    devices: HashMap<PathBuf, Device>,
+    /// Devices that are not gamepads or could not be opened, tried again once the directory
+    /// changes
+    rejected: HashSet<PathBuf>,
+    /// Modification time of the directory when `rejected` was last cleared
+    dir_modified: Option<SystemTime>,
+    next_id: u32,
+    last_scan: Option<Instant>,
+    /// Time between two looks for newly plugged devices
+    pub scan_interval: Duration,
+}
+
+impl Default for EvdevBackend {
+    fn default() -> Self {
+        Self {
+            devices: HashMap::new(),
+            rejected: HashSet::new(),
+            dir_modified: None,
+            next_id: 0,
+            last_scan: None,
+            scan_interval: Duration::from_secs(1),
+        }
+    }
+}
+
+impl GamepadBackend for EvdevBackend {
+    fn poll(&mut self, inputs: &mut Vec<GamepadInput>) {
+        if self
+            .last_scan
+            .is_none_or(|last_scan| last_scan.elapsed() >= self.scan_interval)
+        {
+            self.last_scan = Some(Instant::now());
+            self.scan(Path::new(INPUT_DIR), inputs);
+        }
+
+        self.devices.retain(|_, device| {
+            let connected = device.read(inputs);
+            if !connected {
+                inputs.push(GamepadInput::Disconnected { id: device.id });
+            }
+            connected
+        });
+    }
+}
+
+impl EvdevBackend {
+    fn scan(&mut self, dir: &Path, inputs: &mut Vec<GamepadInput>) {
+        let modified = std::fs::metadata(dir)
+            .and_then(|metadata| metadata.modified())
+            .ok();
+        if modified.is_none() || modified != self.dir_modified {
+            self.rejected.clear();
+            self.dir_modified = modified;
+        }
+
+        let entries = match std::fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(_) => return,
+        };
+        for path in entries.filter_map(|entry| Some(entry.ok()?.path())) {
+            let is_event_device = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("event"));
+            if !is_event_device || self.devices.contains_key(&path) || self.rejected.contains(&path)
+            {
+                continue;
+            }
+            let id = GamepadId(self.next_id);
+            match Device::open(&path, id) {
+                Some((device, name)) => {
+                    self.next_id += 1;
+                    inputs.push(GamepadInput::Connected { id, name });
+                    self.devices.insert(path, device);
+                }
+                None => {
+                    self.rejected.insert(path);
+                }
+            }
+        }
+    }
+}
+
+/// Range reported by the kernel for an absolute axis
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct AbsRange {
+    minimum: i32,
+    maximum: i32,
+}
+
+impl AbsRange {
+    /// Maps a raw value to -1..1 for sticks and 0..1 for triggers
+    fn normalize(&self, value: i32, trigger: bool) -> f32 {
+        let span = (self.maximum - self.minimum).max(1) as f32;
+        let unit = ((value - self.minimum) as f32 / span).clamp(0.0, 1.0);
+        if trigger {
+            unit
+        } else {
+            unit * 2.0 - 1.0
+        }
+    }
+}
+
+struct Device {
+    id: GamepadId,
+    file: File,
+    ranges: HashMap<u16, AbsRange>,
+    /// Room for `READ_EVENTS` raw `input_event`s
+    buffer: Vec<u8>,
+}
+
+impl Device {
+    fn open(path: &Path, id: GamepadId) -> Option<(Self, String)> {
+        let file = OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(path)
+            .ok()?;
+        let fd = file.as_raw_fd();
+
+        let mut keys = [0u8; KEY_MAX / 8 + 1];
+        // EVIOCGBIT(EV_KEY, len)
+        let request = ioc_read(0x20 + EV_KEY as u32, keys.len());
+        if unsafe { libc::ioctl(fd, request as _, keys.as_mut_ptr()) } < 0 {
+            return None;
+        }
+        let gamepad = BTN_GAMEPAD as usize;
+        if keys[gamepad / 8] & (1 << (gamepad % 8)) == 0 {
+            return None;
+        }
+
+        let mut name = [0u8; 128];
+        // EVIOCGNAME(len)
+        let request = ioc_read(0x06, name.len());
+        let name = if unsafe { libc::ioctl(fd, request as _, name.as_mut_ptr()) } > 0 {
+            let end = name
+                .iter()
+                .position(|byte| *byte == 0)
+                .unwrap_or(name.len());
+            String::from_utf8_lossy(&name[..end]).into_owned()
+        } else {
+            path.display().to_string()
+        };
+
+        let ranges = [ABS_X, ABS_Y, ABS_Z, ABS_RX, ABS_RY, ABS_RZ]
+            .into_iter()
+            .filter_map(|axis| {
+                let mut info: libc::input_absinfo = unsafe { std::mem::zeroed() };
+                // EVIOCGABS(axis)
+                let request = ioc_read(0x40 + axis as u32, size_of::<libc::input_absinfo>());
+                let result = unsafe { libc::ioctl(fd, request as _, &mut info) };
+                (result >= 0).then_some((
+                    axis,
+                    AbsRange {
+                        minimum: info.minimum,
+                        maximum: info.maximum,
+                    },
+                ))
+            })
+            .collect();
+
+        let buffer = vec![0u8; size_of::<libc::input_event>() * READ_EVENTS];
+        Some((
+            Self {
+                id,
+                file,
+                ranges,
+                buffer,
+            },
+            name,
+        ))
+    }
+
+    /// Reads the pending events, returns whether the device is still connected
+    fn read(&mut self, inputs: &mut Vec<GamepadInput>) -> bool {
+        let size = size_of::<libc::input_event>();
+        loop {
+            let read = match self.file.read(&mut self.buffer) {
+                Ok(0) => return false,
+                Ok(read) => read,
+                Err(error) if error.kind() == ErrorKind::WouldBlock => return true,
+                Err(error) if error.kind() == ErrorKind::Interrupted => continue,
+                Err(_) => return false,
+            };
+            for event in self.buffer[..read].chunks_exact(size) {
+                // `type`, `code` and `value` follow the timestamp
+                let tail = &event[size - 8..];
+                let kind = u16::from_ne_bytes([tail[0], tail[1]]);
+                let code = u16::from_ne_bytes([tail[2], tail[3]]);
+                let value = i32::from_ne_bytes([tail[4], tail[5], tail[6], tail[7]]);
+                self.translate(kind, code, value, inputs);
+            }
+        }
+    }
+
+    fn translate(&self, kind: u16, code: u16, value: i32, inputs: &mut Vec<GamepadInput>) {
+        let id = self.id;
+        match kind {
+            EV_KEY => {
+                if let Some(button) = map_button(code) {
+                    inputs.push(GamepadInput::Button {
+                        id,
+                        button,
+                        pressed: value != 0,
+                    });
+                }
+            }
+            EV_ABS => match code {
+                ABS_HAT0X | ABS_HAT0Y => {
+                    let (negative, positive) = if code == ABS_HAT0X {
+                        (GamepadButton::DPadLeft, GamepadButton::DPadRight)
+                    } else {
+                        (GamepadButton::DPadUp, GamepadButton::DPadDown)
+                    };
+                    for (button, pressed) in [(negative, value < 0), (positive, value > 0)] {
+                        inputs.push(GamepadInput::Button {
+                            id,
+                            button,
+                            pressed,
+                        });
+                    }
+                }
+                _ => {
+                    if let (Some(axis), Some(range)) = (map_axis(code), self.ranges.get(&code)) {
+                        let mut value = range.normalize(value, axis.is_trigger());
+                        // evdev reports down as positive
+                        if matches!(axis, GamepadAxis::LeftStickY | GamepadAxis::RightStickY) {
+                            value = -value;
+                        }
+                        inputs.push(GamepadInput::Axis { id, axis, value });
+                    }
+                }
+            },
+            _ => {}
+        }
+    }
+}
+
+/// `_IOC(_IOC_READ, 'E', number, size)`
+fn ioc_read(number: u32, size: usize) -> u64 {
+    (2 << 30) | ((size as u64) << 16) | ((b'E' as u64) << 8) | number as u64
+}
+
+fn map_button(code: u16) -> Option<GamepadButton> {
+    Some(match code {
+        0x130 => GamepadButton::South,
+        0x131 => GamepadButton::East,
+        0x133 => GamepadButton::North,
+        0x134 => GamepadButton::West,
+        0x136 => GamepadButton::LeftBumper,
+        0x137 => GamepadButton::RightBumper,
+        0x138 => GamepadButton::LeftTrigger,
+        0x139 => GamepadButton::RightTrigger,
+        0x13a => GamepadButton::Select,
+        0x13b => GamepadButton::Start,
+        0x13c => GamepadButton::Mode,
+        0x13d => GamepadButton::LeftThumb,
+        0x13e => GamepadButton::RightThumb,
+        0x220 => GamepadButton::DPadUp,
+        0x221 => GamepadButton::DPadDown,
+        0x222 => GamepadButton::DPadLeft,
+        0x223 => GamepadButton::DPadRight,
+        _ => return None,
+    })
+}
+
+fn map_axis(code: u16) -> Option<GamepadAxis> {
+    Some(match code {
+        ABS_X => GamepadAxis::LeftStickX,
+        ABS_Y => GamepadAxis::LeftStickY,
+        ABS_RX => GamepadAxis::RightStickX,
+        ABS_RY => GamepadAxis::RightStickY,
+        ABS_Z => GamepadAxis::LeftTrigger,
+        ABS_RZ => GamepadAxis::RightTrigger,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ioctl_requests_match_the_kernel_headers() {
+        // EVIOCGNAME(256) and EVIOCGABS(ABS_X) from linux/input.h
+        assert_eq!(ioc_read(0x06, 256), 0x8100_4506);
+        assert_eq!(ioc_read(0x40, 24), 0x8018_4540);
+    }
+
+    #[test]
+    fn axes_are_normalized_to_their_range() {
+        let stick = AbsRange {
+            minimum: -32768,
+            maximum: 32767,
+        };
+        assert_eq!(stick.normalize(-32768, false), -1.0);
+        assert_eq!(stick.normalize(32767, false), 1.0);
+        let trigger = AbsRange {
+            minimum: 0,
+            maximum: 255,
+        };
+        assert_eq!(trigger.normalize(255, true), 1.0);
+        assert_eq!(trigger.normalize(0, true), 0.0);
+    }
+
+    #[test]
+    fn hat_axes_become_dpad_buttons() {
+        let device = Device {
+            id: GamepadId(0),
+            file: File::open("/dev/null").unwrap(),
+            ranges: HashMap::new(),
+            buffer: Vec::new(),
+        };
+        let mut inputs = Vec::new();
+        device.translate(EV_ABS, ABS_HAT0Y, -1, &mut inputs);
+        assert_eq!(
+            inputs,
+            vec![
+                GamepadInput::Button {
+                    id: GamepadId(0),
+                    button: GamepadButton::DPadUp,
+                    pressed: true,
+                },
+                GamepadInput::Button {
+                    id: GamepadId(0),
+                    button: GamepadButton::DPadDown,
+                    pressed: false,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn rejected_devices_are_skipped_until_the_directory_changes() {
+        let dir = std::env::temp_dir().join(format!("evdev_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir(&dir).unwrap();
+        // A regular file answers the ioctls with an error, like a device that is not a gamepad
+        File::create(dir.join("event0")).unwrap();
+
+        let mut backend = EvdevBackend::default();
+        let mut inputs = Vec::new();
+        backend.scan(&dir, &mut inputs);
+        assert!(inputs.is_empty());
+        assert!(backend.rejected.contains(&dir.join("event0")));
+
+        let unplugged = dir.join("event1");
+        backend.rejected.insert(unplugged.clone());
+        backend.scan(&dir, &mut inputs);
+        assert!(backend.rejected.contains(&unplugged));
+
+        // As if a device was plugged in since
+        backend.dir_modified = Some(SystemTime::UNIX_EPOCH);
+        backend.scan(&dir, &mut inputs);
+        assert!(!backend.rejected.contains(&unplugged));
+        assert!(backend.rejected.contains(&dir.join("event0")));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
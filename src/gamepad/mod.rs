@@ -0,0 +1,413 @@
+#[cfg(target_os = "linux")]
+mod evdev;
+
+use std::collections::BTreeMap;
+
+use crate::prelude::*;
+
+#[cfg(target_os = "linux")]
+pub use evdev::EvdevBackend;
+
+/// Chosen by the backend, unique among the connected gamepads
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct GamepadId(pub u32);
+
+/// Named after the position on the pad, `South` is A on Xbox and Cross on PlayStation pads
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GamepadButton {
+    South = 0,
+    East,
+    North,
+    West,
+    LeftBumper,
+    RightBumper,
+    LeftTrigger,
+    RightTrigger,
+    Select,
+    Start,
+    Mode,
+    LeftThumb,
+    RightThumb,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+}
+
+impl GamepadButton {
+    pub const COUNT: usize = 17;
+}
+
+/// Sticks go from -1 to 1, up and right being positive, triggers from 0 to 1
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GamepadAxis {
+    LeftStickX = 0,
+    LeftStickY,
+    RightStickX,
+    RightStickY,
+    LeftTrigger,
+    RightTrigger,
+}
+
+impl GamepadAxis {
+    pub const COUNT: usize = 6;
+
+    pub fn is_trigger(&self) -> bool {
+        matches!(self, GamepadAxis::LeftTrigger | GamepadAxis::RightTrigger)
+    }
+}
+
+/// Raw state change reported by a `GamepadBackend`
+#[derive(Debug, Clone, PartialEq)]
+pub enum GamepadInput {
+    Connected {
+        id: GamepadId,
+        name: String,
+    },
+    Disconnected {
+        id: GamepadId,
+    },
+    Button {
+        id: GamepadId,
+        button: GamepadButton,
+        pressed: bool,
+    },
+    /// `value` is normalized to the range of the axis, before any deadzone
+    Axis {
+        id: GamepadId,
+        axis: GamepadAxis,
+        value: f32,
+    },
+}
+
+/// Sent when a gamepad is plugged in or out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GamepadEvent {
+    Connected(GamepadId),
+    Disconnected(GamepadId),
+}
+
+/// Source of the `GamepadInput` of a platform
+pub trait GamepadBackend: Send + Sync + 'static {
+    /// Appends the inputs received since the last poll, called once per frame
+    fn poll(&mut self, inputs: &mut Vec<GamepadInput>);
+}
+
+/// The `GamepadBackend` polled by the `MiniquadPlugin`, insert one to replace the platform one
+pub struct GamepadSource(pub Box<dyn GamepadBackend>);
+
+impl GamepadSource {
+    pub fn new(backend: impl GamepadBackend) -> Self {
+        Self(Box::new(backend))
+    }
+}
+
+impl Default for GamepadSource {
+    #[cfg(target_os = "linux")]
+    fn default() -> Self {
+        Self::new(EvdevBackend::default())
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn default() -> Self {
+        Self::new(NoGamepads)
+    }
+}
+
+/// Backend of the platforms without gamepad support, reports nothing
+#[cfg(not(target_os = "linux"))]
+struct NoGamepads;
+
+#[cfg(not(target_os = "linux"))]
+impl GamepadBackend for NoGamepads {
+    fn poll(&mut self, _inputs: &mut Vec<GamepadInput>) {}
+}
+
+#[derive(Debug, Clone)]
+pub struct Gamepad {
+    pub name: String,
+    pub buttons: [ButtonState; GamepadButton::COUNT],
+    /// With the deadzones of `Gamepads` applied
+    pub axes: [f32; GamepadAxis::COUNT],
+}
+
+impl Gamepad {
+    fn new(name: String) -> Self {
+        Self {
+            name,
+            buttons: [ButtonState::Up; GamepadButton::COUNT],
+            axes: [0.0; GamepadAxis::COUNT],
+        }
+    }
+
+    pub fn button(&self, button: GamepadButton) -> ButtonState {
+        self.buttons[button as usize]
+    }
+
+    pub fn axis(&self, axis: GamepadAxis) -> f32 {
+        self.axes[axis as usize]
+    }
+
+    /// Both axes of the left stick
+    pub fn left_stick(&self) -> Vec2 {
+        Vec2::new(
+            self.axis(GamepadAxis::LeftStickX),
+            self.axis(GamepadAxis::LeftStickY),
+        )
+    }
+
+    pub fn right_stick(&self) -> Vec2 {
+        Vec2::new(
+            self.axis(GamepadAxis::RightStickX),
+            self.axis(GamepadAxis::RightStickY),
+        )
+    }
+}
+
+/// Connected gamepads, updated at the start of every frame
+#[derive(Debug, Clone)]
+pub struct Gamepads {
+    gamepads: BTreeMap<GamepadId, Gamepad>,
+    /// Stick values closer to 0 are read as 0, the rest is rescaled to still reach 1
+    pub stick_deadzone: f32,
+    /// Like `stick_deadzone`, for the triggers
+    pub trigger_deadzone: f32,
+}
+
+impl Default for Gamepads {
+    fn default() -> Self {
+        Self {
+            gamepads: BTreeMap::new(),
+            stick_deadzone: 0.15,
+            trigger_deadzone: 0.05,
+        }
+    }
+}
+
+impl Gamepads {
+    /// Ordered by id
+    pub fn iter(&self) -> impl Iterator<Item = (GamepadId, &Gamepad)> {
+        self.gamepads.iter().map(|(id, gamepad)| (*id, gamepad))
+    }
+
+    pub fn get(&self, id: GamepadId) -> Option<&Gamepad> {
+        self.gamepads.get(&id)
+    }
+
+    /// The gamepad of lowest id, handy for single player games
+    pub fn first(&self) -> Option<&Gamepad> {
+        self.gamepads.values().next()
+    }
+
+    pub fn len(&self) -> usize {
+        self.gamepads.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.gamepads.is_empty()
+    }
+
+    pub fn long_state(&mut self) {
+        for gamepad in self.gamepads.values_mut() {
+            gamepad
+                .buttons
+                .iter_mut()
+                .for_each(|state| state.long_state());
+        }
+    }
+
+    /// Applies an input, returns the event to send when a gamepad was plugged in or out
+    pub fn apply(&mut self, input: GamepadInput) -> Option<GamepadEvent> {
+        match input {
+            GamepadInput::Connected { id, name } => {
+                let event =
+                    (!self.gamepads.contains_key(&id)).then_some(GamepadEvent::Connected(id));
+                self.gamepads.insert(id, Gamepad::new(name));
+                event
+            }
+            GamepadInput::Disconnected { id } => self
+                .gamepads
+                .remove(&id)
+                .map(|_| GamepadEvent::Disconnected(id)),
+            GamepadInput::Button {
+                id,
+                button,
+                pressed,
+            } => {
+                let state = &mut self.gamepads.get_mut(&id)?.buttons[button as usize];
+                *state = match (*state, pressed) {
+                    (ButtonState::Up | ButtonState::JustUp, true) => ButtonState::JustDown,
+                    (ButtonState::Down | ButtonState::JustDown, false) => ButtonState::JustUp,
+                    (state, _) => state,
+                };
+                None
+            }
+            GamepadInput::Axis { id, axis, value } => {
+                let deadzone = if axis.is_trigger() {
+                    self.trigger_deadzone
+                } else {
+                    self.stick_deadzone
+                };
+                self.gamepads.get_mut(&id)?.axes[axis as usize] = apply_deadzone(value, deadzone);
+                None
+            }
+        }
+    }
+}
+
+fn apply_deadzone(value: f32, deadzone: f32) -> f32 {
+    let deadzone = deadzone.clamp(0.0, 0.99);
+    let magnitude = value.abs().min(1.0);
+    if magnitude <= deadzone {
+        0.0
+    } else {
+        value.signum() * (magnitude - deadzone) / (1.0 - deadzone)
+    }
+}
+
+/// Moves the `Gamepads` to the state reported by the `GamepadSource` since the last frame
+pub fn poll_gamepads(
+    mut source: ResMut<GamepadSource>,
+    mut gamepads: ResMut<Gamepads>,
+    mut events: EventWriter<GamepadEvent>,
+    mut inputs: Local<Vec<GamepadInput>>,
+) {
+    gamepads.long_state();
+    source.0.poll(&mut inputs);
+    for input in inputs.drain(..) {
+        if let Some(event) = gamepads.apply(input) {
+            events.send(event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::VecDeque,
+        sync::{Arc, Mutex},
+    };
+
+    use bevy_ecs::event::Events;
+
+    use super::*;
+
+    /// Backend fed by hand, clones share their queue so one can be kept to drive the other
+    #[derive(Debug, Default, Clone)]
+    struct FakeGamepadBackend {
+        queue: Arc<Mutex<VecDeque<GamepadInput>>>,
+    }
+
+    impl FakeGamepadBackend {
+        /// Reported by the next poll
+        fn push(&self, input: GamepadInput) {
+            self.queue.lock().unwrap().push_back(input);
+        }
+
+        fn connect(&self, id: GamepadId) {
+            self.push(GamepadInput::Connected {
+                id,
+                name: "Fake gamepad".to_string(),
+            });
+        }
+
+        fn disconnect(&self, id: GamepadId) {
+            self.push(GamepadInput::Disconnected { id });
+        }
+
+        fn set_button(&self, id: GamepadId, button: GamepadButton, pressed: bool) {
+            self.push(GamepadInput::Button {
+                id,
+                button,
+                pressed,
+            });
+        }
+
+        fn set_axis(&self, id: GamepadId, axis: GamepadAxis, value: f32) {
+            self.push(GamepadInput::Axis { id, axis, value });
+        }
+    }
+
+    impl GamepadBackend for FakeGamepadBackend {
+        fn poll(&mut self, inputs: &mut Vec<GamepadInput>) {
+            inputs.extend(self.queue.lock().unwrap().drain(..));
+        }
+    }
+
+    #[test]
+    fn fake_backend_drives_the_gamepads() {
+        let fake = FakeGamepadBackend::default();
+        let mut world = World::default();
+        world.insert_resource(GamepadSource::new(fake.clone()));
+        world.insert_resource(Gamepads::default());
+        world.insert_resource(Events::<GamepadEvent>::default());
+        let mut stage = SystemStage::single_threaded().with_system(poll_gamepads);
+        let pad = GamepadId(3);
+
+        fake.connect(pad);
+        fake.set_button(pad, GamepadButton::South, true);
+        fake.set_axis(pad, GamepadAxis::LeftStickX, -1.0);
+        stage.run(&mut world);
+        let gamepad = world.get_resource::<Gamepads>().unwrap().first().unwrap();
+        assert_eq!(gamepad.button(GamepadButton::South), ButtonState::JustDown);
+        assert_eq!(gamepad.left_stick(), Vec2::new(-1.0, 0.0));
+
+        stage.run(&mut world);
+        let gamepad = world.get_resource::<Gamepads>().unwrap().get(pad).unwrap();
+        assert_eq!(gamepad.button(GamepadButton::South), ButtonState::Down);
+
+        fake.disconnect(pad);
+        stage.run(&mut world);
+        assert!(world.get_resource::<Gamepads>().unwrap().is_empty());
+
+        let events = world.get_resource::<Events<GamepadEvent>>().unwrap();
+        let mut reader = events.get_reader();
+        let sent: Vec<_> = reader.iter(events).copied().collect();
+        assert_eq!(
+            sent,
+            vec![
+                GamepadEvent::Connected(pad),
+                GamepadEvent::Disconnected(pad)
+            ]
+        );
+    }
+
+    #[test]
+    fn deadzones_rescale_the_axes() {
+        assert_eq!(apply_deadzone(0.1, 0.15), 0.0);
+        assert_eq!(apply_deadzone(-1.0, 0.15), -1.0);
+        assert!((apply_deadzone(0.575, 0.15) - 0.5).abs() < 1e-6);
+
+        let mut gamepads = Gamepads::default();
+        let pad = GamepadId(0);
+        gamepads.apply(GamepadInput::Connected {
+            id: pad,
+            name: String::new(),
+        });
+        gamepads.apply(GamepadInput::Axis {
+            id: pad,
+            axis: GamepadAxis::RightTrigger,
+            value: 0.04,
+        });
+        assert_eq!(
+            gamepads.get(pad).unwrap().axis(GamepadAxis::RightTrigger),
+            0.0
+        );
+    }
+
+    #[test]
+    fn inputs_of_unknown_gamepads_are_ignored() {
+        let mut gamepads = Gamepads::default();
+        let event = gamepads.apply(GamepadInput::Button {
+            id: GamepadId(1),
+            button: GamepadButton::Start,
+            pressed: true,
+        });
+        assert_eq!(event, None);
+        assert!(gamepads.is_empty());
+        assert_eq!(
+            gamepads.apply(GamepadInput::Disconnected { id: GamepadId(1) }),
+            None
+        );
+    }
+}
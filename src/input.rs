@@ -38,17 +38,58 @@ pub struct Mouse {
     pub left: ButtonState,
     pub right: ButtonState,
     pub middle: ButtonState,
+    /// Scrolled this frame, positive `y` scrolls up
+    pub wheel: Vec2,
+    /// How far `pos` moved this frame, in window pixels
+    pub delta: Vec2,
+    /// Unaccelerated motion of the device this frame, keeps going when the cursor hits the edge
+    /// of the window
+    pub raw_motion: Vec2,
+    /// Whether `pos` was reported by the platform yet
+    located: bool,
+}
+
+impl Mouse {
+    pub fn long_state(&mut self) {
+        self.left.long_state();
+        self.right.long_state();
+        self.middle.long_state();
+        self.wheel = Vec2::ZERO;
+        self.delta = Vec2::ZERO;
+        self.raw_motion = Vec2::ZERO;
+    }
+
+    /// Moves the cursor to `pos`, the first position reported is not counted as motion
+    pub fn move_to(&mut self, pos: Vec2) {
+        if self.located {
+            self.delta += pos - self.pos;
+        }
+        self.pos = pos;
+        self.located = true;
+    }
+
+    /// Places the cursor at `pos` without any motion, for a pointer that reappears elsewhere
+    pub fn jump_to(&mut self, pos: Vec2) {
+        self.pos = pos;
+        self.located = true;
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
 pub struct KeyboardInput {
     pub keys: [ButtonState; 128],
+    /// Keys the platform sent a repeat for this frame while held down
+    pub repeated: [bool; 128],
+    /// Held down as of the last keyboard event
+    pub modifiers: Modifiers,
 }
 
 impl Default for KeyboardInput {
     fn default() -> Self {
         Self {
             keys: [ButtonState::Up; 128],
+            repeated: [false; 128],
+            modifiers: Modifiers::default(),
         }
     }
 }
@@ -56,6 +97,51 @@ impl Default for KeyboardInput {
 impl KeyboardInput {
     pub fn long_state(&mut self) {
         self.keys.iter_mut().for_each(|state| state.long_state());
+        self.repeated = [false; 128];
+    }
+
+    /// Whether `key` is `JustDown` or repeated this frame, e.g. for text cursors and undo
+    pub fn pressed_or_repeated(&self, key: KeyboardKey) -> bool {
+        self.get(key) == ButtonState::JustDown || self.repeated(key)
+    }
+
+    pub fn repeated(&self, key: KeyboardKey) -> bool {
+        let index: usize = key.into();
+        self.repeated[index]
+    }
+
+    /// Handles a key down event, repeats of a held key keep it `Down`
+    pub fn press(&mut self, key: Option<KeyboardKey>, modifiers: Modifiers, repeat: bool) {
+        self.modifiers = modifiers;
+        let key = match key {
+            Some(key) => key,
+            None => return,
+        };
+        // Platforms report the modifiers held before the event
+        if let Some(modifier) = self.modifiers.get_mut(key) {
+            *modifier = true;
+        }
+        let index: usize = key.into();
+        match self.keys[index] {
+            ButtonState::JustDown | ButtonState::Down if repeat => self.repeated[index] = true,
+            _ => self.keys[index] = ButtonState::JustDown,
+        }
+    }
+
+    /// Handles a key up event
+    pub fn release(&mut self, key: Option<KeyboardKey>, modifiers: Modifiers) {
+        self.modifiers = modifiers;
+        if let Some(key) = key {
+            self.set(key, ButtonState::JustUp);
+            // The modifiers reported still include the released key, the modifier is only held
+            // on if the key on the other side is down too
+            let other_side_down = Modifiers::other_side(key).is_some_and(|other| {
+                matches!(self.get(other), ButtonState::JustDown | ButtonState::Down)
+            });
+            if let Some(modifier) = self.modifiers.get_mut(key) {
+                *modifier &= other_side_down;
+            }
+        }
     }
 
     pub fn get(&self, key: KeyboardKey) -> ButtonState {
@@ -71,6 +157,171 @@ impl KeyboardInput {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TouchPhase {
+    /// Touched down this frame
+    Started,
+    /// Down since an earlier frame, `Touch::delta` tells whether it moved this frame
+    Moved,
+    /// Lifted this frame, the touch is gone next frame
+    Ended,
+    /// Interrupted this frame by the platform, e.g. by a system gesture, the touch is gone next
+    /// frame
+    Cancelled,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Touch {
+    /// Unique among the active touches, may be reused once a touch is gone
+    pub id: u64,
+    /// In window pixels from the top left corner, like `Mouse::pos`
+    pub start_pos: Vec2,
+    pub pos: Vec2,
+    /// How far the touch moved this frame
+    pub delta: Vec2,
+    pub phase: TouchPhase,
+}
+
+/// Active touches, plus the ones that ended this frame
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Touches {
+    touches: [Option<Touch>; Touches::MAX],
+}
+
+impl Touches {
+    /// Touches past this many at once are ignored
+    pub const MAX: usize = 10;
+
+    pub fn iter(&self) -> impl Iterator<Item = &Touch> {
+        self.touches.iter().flatten()
+    }
+
+    pub fn get(&self, id: u64) -> Option<&Touch> {
+        self.iter().find(|touch| touch.id == id)
+    }
+
+    pub fn just_started(&self) -> impl Iterator<Item = &Touch> {
+        self.iter()
+            .filter(|touch| touch.phase == TouchPhase::Started)
+    }
+
+    pub fn len(&self) -> usize {
+        self.iter().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Handles a touch event, returns the updated touch
+    pub fn update(&mut self, id: u64, pos: Vec2, phase: TouchPhase) -> Option<Touch> {
+        let slot = match self
+            .touches
+            .iter()
+            .position(|touch| matches!(touch, Some(touch) if touch.id == id))
+        {
+            Some(index) => &mut self.touches[index],
+            None if phase == TouchPhase::Started => {
+                let index = self.touches.iter().position(Option::is_none)?;
+                self.touches[index] = Some(Touch {
+                    id,
+                    start_pos: pos,
+                    pos,
+                    delta: Vec2::ZERO,
+                    phase,
+                });
+                return self.touches[index];
+            }
+            None => return None,
+        };
+        let touch = slot.as_mut()?;
+        touch.delta += pos - touch.pos;
+        touch.pos = pos;
+        // A touch that started and moved within a frame still reports `Started`
+        if phase != TouchPhase::Moved || touch.phase != TouchPhase::Started {
+            touch.phase = phase;
+        }
+        Some(*touch)
+    }
+
+    pub fn long_state(&mut self) {
+        for slot in self.touches.iter_mut() {
+            match slot {
+                Some(Touch {
+                    phase: TouchPhase::Ended | TouchPhase::Cancelled,
+                    ..
+                }) => *slot = None,
+                Some(touch) => {
+                    touch.phase = TouchPhase::Moved;
+                    touch.delta = Vec2::ZERO;
+                }
+                None => {}
+            }
+        }
+    }
+}
+
+/// Modifier keys held during a keyboard event
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub ctrl: bool,
+    pub alt: bool,
+    /// The Windows, Command or Super key
+    pub logo: bool,
+}
+
+impl Modifiers {
+    /// Whether no modifier is held
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// The modifier held by `key`, if it is a modifier key
+    fn get_mut(&mut self, key: KeyboardKey) -> Option<&mut bool> {
+        match key {
+            KeyboardKey::LeftShift | KeyboardKey::RightShift => Some(&mut self.shift),
+            KeyboardKey::LeftControl | KeyboardKey::RightControl => Some(&mut self.ctrl),
+            KeyboardKey::LeftAlt | KeyboardKey::RightAlt => Some(&mut self.alt),
+            KeyboardKey::LeftSuper | KeyboardKey::RightSuper => Some(&mut self.logo),
+            _ => None,
+        }
+    }
+
+    /// The key on the other side of the keyboard holding the same modifier as `key`
+    fn other_side(key: KeyboardKey) -> Option<KeyboardKey> {
+        match key {
+            KeyboardKey::LeftShift => Some(KeyboardKey::RightShift),
+            KeyboardKey::RightShift => Some(KeyboardKey::LeftShift),
+            KeyboardKey::LeftControl => Some(KeyboardKey::RightControl),
+            KeyboardKey::RightControl => Some(KeyboardKey::LeftControl),
+            KeyboardKey::LeftAlt => Some(KeyboardKey::RightAlt),
+            KeyboardKey::RightAlt => Some(KeyboardKey::LeftAlt),
+            KeyboardKey::LeftSuper => Some(KeyboardKey::RightSuper),
+            KeyboardKey::RightSuper => Some(KeyboardKey::LeftSuper),
+            _ => None,
+        }
+    }
+}
+
+/// Event sent for every text character typed, in typing order, once composed by the platform
+/// input method. Control characters such as backspace only arrive as key presses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReceivedCharacter {
+    pub character: char,
+    pub modifiers: Modifiers,
+    /// Sent again because the key is held down
+    pub repeat: bool,
+}
+
+impl ReceivedCharacter {
+    /// Whether the character belongs in a text field, unlike control characters such as
+    /// backspace or escape
+    pub fn is_text(&self) -> bool {
+        !self.character.is_control()
+    }
+}
+
 #[derive(Debug, Default, Clone, Copy)]
 pub struct Window {
     pub width: f32,
@@ -83,17 +334,18 @@ pub struct FrameInput {
     pub window: Window,
     pub mouse: Mouse,
     pub keyboard: KeyboardInput,
+    pub touches: Touches,
 }
 
 impl FrameInput {
     pub fn long_state(&mut self) {
         self.keyboard.long_state();
-        self.mouse.left.long_state();
-        self.mouse.right.long_state();
-        self.mouse.middle.long_state();
+        self.mouse.long_state();
+        self.touches.long_state();
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum KeyboardKey {
     Space = 0,
     Apostrophe,
@@ -222,3 +474,118 @@ impl From<KeyboardKey> for usize {
         val as usize
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mouse_motion_lasts_a_frame() {
+        let mut frame_input = FrameInput::default();
+        frame_input.mouse.move_to(Vec2::new(10.0, 5.0));
+        frame_input.mouse.move_to(Vec2::new(12.0, 4.0));
+        frame_input.mouse.wheel += Vec2::new(0.0, 1.0);
+        frame_input.mouse.left = ButtonState::JustDown;
+        assert_eq!(frame_input.mouse.delta, Vec2::new(2.0, -1.0));
+
+        frame_input.long_state();
+        assert_eq!(frame_input.mouse.pos, Vec2::new(12.0, 4.0));
+        assert_eq!(frame_input.mouse.delta, Vec2::ZERO);
+        assert_eq!(frame_input.mouse.wheel, Vec2::ZERO);
+        assert_eq!(frame_input.mouse.left, ButtonState::Down);
+    }
+
+    #[test]
+    fn jumps_are_not_motion() {
+        let mut mouse = Mouse::default();
+        mouse.move_to(Vec2::new(10.0, 5.0));
+        mouse.jump_to(Vec2::new(300.0, 200.0));
+        mouse.move_to(Vec2::new(301.0, 200.0));
+        assert_eq!(mouse.delta, Vec2::new(1.0, 0.0));
+        assert_eq!(mouse.pos, Vec2::new(301.0, 200.0));
+    }
+
+    #[test]
+    fn repeats_are_not_new_presses() {
+        let mut keyboard = KeyboardInput::default();
+        keyboard.press(Some(KeyboardKey::Z), Modifiers::default(), false);
+        keyboard.long_state();
+        keyboard.press(Some(KeyboardKey::Z), Modifiers::default(), true);
+        assert_eq!(keyboard.get(KeyboardKey::Z), ButtonState::Down);
+        assert!(keyboard.pressed_or_repeated(KeyboardKey::Z));
+
+        keyboard.long_state();
+        assert!(!keyboard.repeated(KeyboardKey::Z));
+        assert!(!keyboard.pressed_or_repeated(KeyboardKey::Z));
+    }
+
+    #[test]
+    fn modifier_keys_update_the_modifiers() {
+        let mut keyboard = KeyboardInput::default();
+        keyboard.press(Some(KeyboardKey::LeftControl), Modifiers::default(), false);
+        assert!(keyboard.modifiers.ctrl);
+
+        let ctrl = keyboard.modifiers;
+        keyboard.press(Some(KeyboardKey::Z), ctrl, false);
+        assert!(keyboard.modifiers.ctrl && keyboard.get(KeyboardKey::Z) == ButtonState::JustDown);
+
+        keyboard.release(Some(KeyboardKey::LeftControl), ctrl);
+        assert!(keyboard.modifiers.is_empty());
+    }
+
+    #[test]
+    fn modifiers_stay_held_by_the_other_side() {
+        let mut keyboard = KeyboardInput::default();
+        keyboard.press(Some(KeyboardKey::LeftShift), Modifiers::default(), false);
+        let shift = keyboard.modifiers;
+        keyboard.press(Some(KeyboardKey::RightShift), shift, false);
+
+        keyboard.release(Some(KeyboardKey::LeftShift), shift);
+        assert!(keyboard.modifiers.shift);
+        keyboard.release(Some(KeyboardKey::RightShift), shift);
+        assert!(!keyboard.modifiers.shift);
+    }
+
+    #[test]
+    fn touches_are_tracked_by_id() {
+        let mut touches = Touches::default();
+        touches.update(7, Vec2::new(1.0, 1.0), TouchPhase::Started);
+        touches.update(3, Vec2::new(5.0, 5.0), TouchPhase::Started);
+        touches.update(7, Vec2::new(2.0, 4.0), TouchPhase::Moved);
+        assert_eq!(touches.just_started().count(), 2);
+        assert_eq!(touches.get(7).unwrap().delta, Vec2::new(1.0, 3.0));
+
+        touches.long_state();
+        touches.update(3, Vec2::new(5.0, 6.0), TouchPhase::Cancelled);
+        let touch = *touches.get(3).unwrap();
+        assert_eq!(touch.start_pos, Vec2::new(5.0, 5.0));
+        assert_eq!(touch.phase, TouchPhase::Cancelled);
+        assert_eq!(touches.get(7).unwrap().phase, TouchPhase::Moved);
+
+        touches.long_state();
+        assert_eq!(touches.len(), 1);
+        assert!(touches.update(3, Vec2::ZERO, TouchPhase::Moved).is_none());
+    }
+
+    #[test]
+    fn touches_past_the_maximum_are_ignored() {
+        let mut touches = Touches::default();
+        for id in 0..=Touches::MAX as u64 {
+            touches.update(id, Vec2::ZERO, TouchPhase::Started);
+        }
+        assert_eq!(touches.len(), Touches::MAX);
+        assert!(touches.get(Touches::MAX as u64).is_none());
+    }
+
+    #[test]
+    fn control_characters_are_not_text() {
+        let typed = |character| ReceivedCharacter {
+            character,
+            modifiers: Modifiers::default(),
+            repeat: false,
+        };
+        assert!(typed('é').is_text());
+        assert!(!typed('\u{8}').is_text());
+        assert!(!typed('\u{1b}').is_text());
+    }
+}
@@ -1,4 +1,7 @@
 mod color;
+// Read by game systems through the prelude; the demo below doesn't poll gamepads.
+#[allow(dead_code)]
+mod gamepad;
 mod input;
 
 pub mod components;
@@ -15,8 +18,18 @@ mod prelude {
 
     pub use crate::color::*;
     pub use crate::components::*;
+    pub use crate::gamepad::{
+        Gamepad, GamepadAxis, GamepadButton, GamepadEvent, GamepadId, Gamepads,
+    };
     pub use crate::input::*;
-    pub use crate::mq::{DebugShape2D, DebugText, MiniquadPlugin};
+    pub use crate::mq::{
+        Anchor, AnimationClip, AnimationCompleted, AnimationFrame, Assets, Camera, CameraView,
+        Cameras, ClearColor, ClearColorConfig, DebugShape2D, DebugText, DrawType, Font, FontError,
+        Fonts, Handle, HotReload, HotReloadPlugin, Image, ImageFormat, InputSettings, LoadState,
+        Material, MaterialPipelines, MaterialPlugin, MeshData, MiniquadPlugin, PlaybackMode, Ray,
+        Rect, RenderLayer, RenderLayers, RenderSettings, RenderStats, ShaderSource, TextDimensions,
+        TextParams, TextureAtlas, TextureError, WindowDescriptor,
+    };
 }
 
 use crate::prelude::*;
@@ -24,17 +37,11 @@ use crate::prelude::*;
 #[derive(Clone, Copy, Debug, Component)]
 pub struct WaveQuad;
 
-pub fn load_square(mut commands: Commands, mut ctx: ResMut<miniquad::Context>) {
-    use mq::shaders::Vertex;
-    #[rustfmt::skip]
-        let vertices: [Vertex; 4] = [
-            Vertex { position: Vec3::new(-0.5, -0.5, 0.0 ), uv: Vec2::new(0., 0.) },
-            Vertex { position: Vec3::new( 0.5, -0.5, 0.0 ), uv: Vec2::new(1., 0.) },
-            Vertex { position: Vec3::new( 0.5,  0.5, 0.0 ), uv: Vec2::new(1., 1.) },
-            Vertex { position: Vec3::new(-0.5,  0.5, 0.0 ), uv: Vec2::new(0., 1.) },
-        ];
-
-    let indices: [u16; 6] = [0, 1, 2, 0, 2, 3];
+pub fn load_square(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<SimpleMesh>>,
+    mut textures: ResMut<Assets<SimpleMeshTexture>>,
+) {
     let pixels: [u8; 4 * 4 * 4] = [
         0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x00, 0x00,
         0xFF, 0xFF, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x00, 0x00, 0xFF, 0xFF, 0xFF,
@@ -43,17 +50,19 @@ pub fn load_square(mut commands: Commands, mut ctx: ResMut<miniquad::Context>) {
         0xFF, 0xFF, 0xFF, 0xFF,
     ];
 
-    let mesh = SimpleMesh::new(&mut ctx, &vertices, &indices);
-    let tex: SimpleMeshTexture = SimpleMeshTexture::from_data(
-        &mut ctx,
-        &pixels,
-        miniquad::TextureParams {
-            width: 4,
-            height: 4,
-            filter: miniquad::FilterMode::Nearest,
-            ..Default::default()
-        },
-    );
+    let mesh = meshes.add_deferred(|ctx| Ok(SimpleMesh::quad(ctx, Vec2::ONE)));
+    let tex = textures.add_deferred(move |ctx| {
+        Ok(SimpleMeshTexture::from_data(
+            ctx,
+            &pixels,
+            miniquad::TextureParams {
+                width: 4,
+                height: 4,
+                filter: miniquad::FilterMode::Nearest,
+                ..Default::default()
+            },
+        ))
+    });
 
     for i in 0..10 {
         let t = i as f32 * 0.3;
@@ -64,15 +73,15 @@ pub fn load_square(mut commands: Commands, mut ctx: ResMut<miniquad::Context>) {
         let transform = Transform::from_translation(translation);
         commands
             .spawn()
-            .insert_bundle((mesh.clone(), color, tex.clone(), transform));
+            .insert_bundle((mesh, color, tex, transform));
     }
-    let camera_transform = Transform::from_scale(Vec3::new(5.0, 5.0, 1.0));
-    commands.spawn_bundle((camera_transform, Projection::default()));
+    let projection = Projection::Orthographic(ScalingMode::FixedHeight(9.0));
+    commands.spawn_bundle((Transform::identity(), projection));
 }
 
 pub fn wave_quad(
     frame_input: Res<FrameInput>,
-    mut query: Query<(&mut Transform, &mut MeshColor), With<SimpleMesh>>,
+    mut query: Query<(&mut Transform, &mut MeshColor), With<Handle<SimpleMesh>>>,
 ) {
     let t = frame_input.time.time_in_seconds_since_start;
     query
@@ -90,6 +99,10 @@ pub fn wave_quad(
 
 fn main() {
     App::default()
+        .insert_resource(WindowDescriptor {
+            title: "FlappyBird".to_string(),
+            ..Default::default()
+        })
         .add_plugin(TransformPlugin)
         .add_plugin(MiniquadPlugin::default())
         .add_startup_system(load_square)
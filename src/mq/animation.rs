@@ -0,0 +1,350 @@
+use std::collections::HashMap;
+
+use crate::prelude::*;
+
+use super::sprite::{Rect, Sprite, TextureAtlas};
+
+/// Frames shorter than this are held for this long, so a clip always makes progress
+pub const MIN_FRAME_DURATION: f32 = 0.001;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlaybackMode {
+    /// Restarts from the first frame after the last one
+    Loop,
+    /// Plays forward then backward, a cycle ends back on the first frame
+    PingPong,
+    /// Stops on the last frame
+    Once,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AnimationFrame {
+    /// Region of the sprite texture, in pixels
+    pub rect: Rect,
+    /// Seconds the frame is shown for
+    pub duration: f32,
+}
+
+#[derive(Debug, Clone)]
+pub struct AnimationClip {
+    pub frames: Vec<AnimationFrame>,
+    pub mode: PlaybackMode,
+}
+
+impl AnimationClip {
+    /// A clip without frames, see `with_frame`
+    pub fn new(mode: PlaybackMode) -> Self {
+        Self {
+            frames: Vec::new(),
+            mode,
+        }
+    }
+
+    /// Shows the atlas regions at `indices` for `frame_duration` seconds each, indices outside
+    /// of the atlas are skipped
+    pub fn from_atlas(
+        atlas: &TextureAtlas,
+        indices: impl IntoIterator<Item = usize>,
+        frame_duration: f32,
+        mode: PlaybackMode,
+    ) -> Self {
+        indices
+            .into_iter()
+            .filter_map(|index| atlas.region(index))
+            .fold(Self::new(mode), |clip, rect| {
+                clip.with_frame(rect, frame_duration)
+            })
+    }
+
+    pub fn with_frame(mut self, rect: Rect, duration: f32) -> Self {
+        self.frames.push(AnimationFrame {
+            rect,
+            duration: duration.max(MIN_FRAME_DURATION),
+        });
+        self
+    }
+
+    /// Seconds a single pass over the frames takes
+    pub fn duration(&self) -> f32 {
+        self.frames.iter().map(|frame| frame.duration).sum()
+    }
+}
+
+/// Sent when an `AnimatedSprite` clip finishes, or completes cycles if it repeats, at most once
+/// per frame
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnimationCompleted {
+    pub entity: Entity,
+    pub clip: String,
+    /// Cycles completed since the last frame, more than 1 after a long frame
+    pub cycles: usize,
+}
+
+/// Named clips played on the `Sprite` of the same entity, advanced every frame by the
+/// `MiniquadPlugin`
+#[derive(Debug, Clone, Component)]
+pub struct AnimatedSprite {
+    clips: HashMap<String, AnimationClip>,
+    current: Option<String>,
+    frame: usize,
+    elapsed: f32,
+    forward: bool,
+    finished: bool,
+    /// Multiplies the elapsed time, 0.0 pauses the animation
+    pub speed: f32,
+}
+
+impl Default for AnimatedSprite {
+    fn default() -> Self {
+        Self {
+            clips: HashMap::new(),
+            current: None,
+            frame: 0,
+            elapsed: 0.0,
+            forward: true,
+            finished: false,
+            speed: 1.0,
+        }
+    }
+}
+
+impl AnimatedSprite {
+    pub fn with_clip(mut self, name: impl Into<String>, clip: AnimationClip) -> Self {
+        self.add_clip(name, clip);
+        self
+    }
+
+    /// Adds or replaces a clip, the first clip added starts playing. Replacing the clip that is
+    /// playing restarts it.
+    pub fn add_clip(&mut self, name: impl Into<String>, clip: AnimationClip) {
+        let name = name.into();
+        if self.current.is_none() {
+            self.current = Some(name.clone());
+        }
+        if self.current.as_ref() == Some(&name) {
+            self.restart();
+        }
+        self.clips.insert(name, clip);
+    }
+
+    /// Switches to the clip `name` from its first frame, keeps going if it is already playing.
+    ///
+    /// Returns `false` if there is no such clip.
+    pub fn play(&mut self, name: &str) -> bool {
+        if !self.clips.contains_key(name) {
+            return false;
+        }
+        if self.current.as_deref() != Some(name) {
+            self.current = Some(name.to_string());
+            self.restart();
+        }
+        true
+    }
+
+    /// Plays the current clip again from its first frame
+    pub fn restart(&mut self) {
+        self.frame = 0;
+        self.elapsed = 0.0;
+        self.forward = true;
+        self.finished = false;
+    }
+
+    pub fn current_clip(&self) -> Option<&str> {
+        self.current.as_deref()
+    }
+
+    pub fn current_frame(&self) -> Option<&AnimationFrame> {
+        self.clip().and_then(|clip| clip.frames.get(self.frame))
+    }
+
+    pub fn frame_index(&self) -> usize {
+        self.frame
+    }
+
+    /// Whether a `PlaybackMode::Once` clip reached its end
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    fn clip(&self) -> Option<&AnimationClip> {
+        self.current.as_ref().and_then(|name| self.clips.get(name))
+    }
+
+    /// Moves the animation `delta` seconds forward and returns how many times the current clip
+    /// completed
+    pub fn advance(&mut self, delta: f32) -> usize {
+        let clip = match self.clip() {
+            Some(clip) if !clip.frames.is_empty() && !self.finished => clip,
+            _ => return 0,
+        };
+        let order = play_order(clip.frames.len(), clip.mode);
+        let durations: Vec<f32> = order
+            .iter()
+            .map(|index| clip.frames[*index].duration)
+            .collect();
+        let cycle: f32 = durations.iter().sum();
+        let (frame_count, mode) = (clip.frames.len(), clip.mode);
+
+        // Position of the current frame in `order`, a ping pong clip shows its frames twice
+        let position = if self.forward {
+            Some(self.frame)
+        } else {
+            order.len().checked_sub(self.frame)
+        };
+        let (position, duration) =
+            match position.and_then(|position| Some((position, *durations.get(position)?))) {
+                Some(current) => current,
+                None => return 0,
+            };
+        self.elapsed += delta * self.speed;
+        if self.elapsed < duration {
+            return 0;
+        }
+
+        let mut time = durations[..position].iter().sum::<f32>() + self.elapsed;
+        let completed = (time / cycle) as usize;
+        if completed > 0 && mode == PlaybackMode::Once {
+            self.frame = order.len() - 1;
+            self.elapsed = 0.0;
+            self.finished = true;
+            return 1;
+        }
+        time %= cycle;
+
+        let mut position = 0;
+        while position + 1 < order.len() && time >= durations[position] {
+            time -= durations[position];
+            position += 1;
+        }
+        self.frame = order[position];
+        self.forward = position < frame_count;
+        self.elapsed = time;
+        completed
+    }
+}
+
+/// Frame indices shown during one cycle of a clip
+fn play_order(frame_count: usize, mode: PlaybackMode) -> Vec<usize> {
+    match mode {
+        PlaybackMode::PingPong => (0..frame_count)
+            .chain((1..frame_count.saturating_sub(1)).rev())
+            .collect(),
+        PlaybackMode::Loop | PlaybackMode::Once => (0..frame_count).collect(),
+    }
+}
+
+/// Advances every `AnimatedSprite` by the time elapsed since the last frame
+pub fn animate_sprites(
+    frame_input: Res<FrameInput>,
+    mut last_time: Local<f64>,
+    mut query: Query<(Entity, &mut AnimatedSprite, &mut Sprite)>,
+    mut completed: EventWriter<AnimationCompleted>,
+) {
+    let now = frame_input.time.time_in_seconds_since_start;
+    let delta = (now - *last_time).max(0.0) as f32;
+    *last_time = now;
+
+    for (entity, mut animation, mut sprite) in query.iter_mut() {
+        let cycles = animation.advance(delta);
+        if cycles > 0 {
+            completed.send(AnimationCompleted {
+                entity,
+                clip: animation.current.clone().unwrap_or_default(),
+                cycles,
+            });
+        }
+        if let Some(frame) = animation.current_frame() {
+            sprite.rect = Some(frame.rect);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clip(frames: usize, mode: PlaybackMode) -> AnimationClip {
+        (0..frames).fold(AnimationClip::new(mode), |clip, index| {
+            clip.with_frame(Rect::new(index as f32, 0.0, 1.0, 1.0), 0.25)
+        })
+    }
+
+    fn frames(animation: &mut AnimatedSprite, steps: usize) -> Vec<usize> {
+        (0..steps)
+            .map(|_| {
+                animation.advance(0.25);
+                animation.frame_index()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn loop_wraps_and_completes_every_cycle() {
+        let mut animation =
+            AnimatedSprite::default().with_clip("walk", clip(3, PlaybackMode::Loop));
+        assert_eq!(animation.advance(0.6), 0);
+        assert_eq!(animation.frame_index(), 2);
+        assert_eq!(animation.advance(1.0), 2);
+        assert_eq!(animation.frame_index(), 0);
+    }
+
+    #[test]
+    fn ping_pong_bounces_on_the_last_frame() {
+        let mut animation =
+            AnimatedSprite::default().with_clip("idle", clip(3, PlaybackMode::PingPong));
+        assert_eq!(frames(&mut animation, 6), vec![1, 2, 1, 0, 1, 2]);
+    }
+
+    #[test]
+    fn once_stops_on_the_last_frame() {
+        let mut animation =
+            AnimatedSprite::default().with_clip("explode", clip(2, PlaybackMode::Once));
+        assert_eq!(animation.advance(0.3), 0);
+        assert_eq!(animation.advance(1.0), 1);
+        assert!(animation.is_finished());
+        assert_eq!(animation.frame_index(), 1);
+        assert_eq!(animation.advance(1.0), 0);
+    }
+
+    #[test]
+    fn playing_another_clip_restarts_it() {
+        let mut animation = AnimatedSprite::default()
+            .with_clip("walk", clip(3, PlaybackMode::Loop))
+            .with_clip("jump", clip(2, PlaybackMode::Once));
+        animation.advance(0.3);
+        assert!(animation.play("walk"));
+        assert_eq!(animation.frame_index(), 1);
+
+        assert!(animation.play("jump"));
+        assert_eq!(animation.frame_index(), 0);
+        assert!(!animation.play("swim"));
+        assert_eq!(animation.current_clip(), Some("jump"));
+    }
+
+    #[test]
+    fn replacing_the_playing_clip_restarts_it() {
+        let mut animation =
+            AnimatedSprite::default().with_clip("idle", clip(4, PlaybackMode::PingPong));
+        animation.advance(1.1);
+        assert_eq!((animation.frame_index(), animation.forward), (2, false));
+
+        animation.add_clip("idle", clip(2, PlaybackMode::Loop));
+        assert_eq!(animation.frame_index(), 0);
+        assert_eq!(animation.advance(0.3), 0);
+        assert_eq!(animation.frame_index(), 1);
+    }
+
+    #[test]
+    fn long_stalls_skip_whole_cycles() {
+        let mut animation = AnimatedSprite::default().with_clip(
+            "spin",
+            AnimationClip::new(PlaybackMode::PingPong)
+                .with_frame(Rect::new(0.0, 0.0, 1.0, 1.0), 0.0)
+                .with_frame(Rect::new(1.0, 0.0, 1.0, 1.0), 0.0)
+                .with_frame(Rect::new(2.0, 0.0, 1.0, 1.0), 0.0),
+        );
+        // 4 frames of 1ms per cycle
+        assert_eq!(animation.advance(1000.0025), 250_000);
+        assert_eq!(animation.frame_index(), 2);
+    }
+}
@@ -0,0 +1,373 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    hash::{Hash, Hasher},
+    marker::PhantomData,
+    path::{Path, PathBuf},
+};
+
+use bevy_ecs::component::TableStorage;
+
+use crate::prelude::*;
+
+use super::{
+    components::{SimpleMesh, SimpleMeshTexture},
+    shaders::ShaderSource,
+    Font, Fonts, Image,
+};
+
+/// Refers to an asset stored in `Assets<T>`, valid whether the asset is loaded yet or not
+pub struct Handle<T> {
+    id: usize,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Handle<T> {
+    fn new(id: usize) -> Self {
+        Self {
+            id,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Handle<T> {}
+
+impl<T> PartialEq for Handle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl<T> Eq for Handle<T> {}
+
+impl<T> Hash for Handle<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
+
+impl<T> fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Handle<{}>({})", std::any::type_name::<T>(), self.id)
+    }
+}
+
+impl<T: Send + Sync + 'static> Component for Handle<T> {
+    type Storage = TableStorage;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoadState {
+    /// Waiting for the `AssetLoading` stage
+    Loading,
+    Loaded,
+    /// The reason the asset could not be loaded, a failed reload keeps the previous asset
+    Failed(String),
+}
+
+type Deferred<T> = Box<dyn FnOnce(&mut miniquad::Context) -> Result<T, String> + Send + Sync>;
+
+pub(crate) enum AssetRequest<T> {
+    Path(PathBuf),
+    Deferred(Deferred<T>),
+}
+
+/// Every asset of type `T`, requests are fulfilled during the `AssetLoading` stage
+pub struct Assets<T> {
+    assets: HashMap<usize, T>,
+    states: HashMap<usize, LoadState>,
+    paths: HashMap<usize, PathBuf>,
+    handles_by_path: HashMap<PathBuf, usize>,
+    requests: Vec<(Handle<T>, AssetRequest<T>)>,
+    next_id: usize,
+}
+
+impl<T> Default for Assets<T> {
+    fn default() -> Self {
+        Self {
+            assets: HashMap::new(),
+            states: HashMap::new(),
+            paths: HashMap::new(),
+            handles_by_path: HashMap::new(),
+            requests: Vec::new(),
+            next_id: 0,
+        }
+    }
+}
+
+impl<T> Assets<T> {
+    /// Stores an already created asset
+    pub fn add(&mut self, asset: T) -> Handle<T> {
+        let handle = self.reserve();
+        self.finish(handle, Ok(asset));
+        handle
+    }
+
+    /// Requests the asset at `path`, requesting the same path again returns the same handle.
+    ///
+    /// A path that failed to load is requested again.
+    pub fn load(&mut self, path: impl AsRef<Path>) -> Handle<T> {
+        let path = path.as_ref();
+        if let Some(&id) = self.handles_by_path.get(path) {
+            let handle = Handle::new(id);
+            if let Some(LoadState::Failed(_)) = self.states.get(&id) {
+                self.request(handle, AssetRequest::Path(path.to_path_buf()));
+            }
+            return handle;
+        }
+
+        let handle = self.reserve();
+        self.paths.insert(handle.id, path.to_path_buf());
+        self.handles_by_path.insert(path.to_path_buf(), handle.id);
+        self.request(handle, AssetRequest::Path(path.to_path_buf()));
+        handle
+    }
+
+    /// Creates the asset with the GPU context during the next `AssetLoading` stage
+    pub fn add_deferred(
+        &mut self,
+        create: impl FnOnce(&mut miniquad::Context) -> Result<T, String> + Send + Sync + 'static,
+    ) -> Handle<T> {
+        let handle = self.reserve();
+        self.request(handle, AssetRequest::Deferred(Box::new(create)));
+        handle
+    }
+
+    pub fn get(&self, handle: &Handle<T>) -> Option<&T> {
+        self.assets.get(&handle.id)
+    }
+
+    pub fn get_mut(&mut self, handle: &Handle<T>) -> Option<&mut T> {
+        self.assets.get_mut(&handle.id)
+    }
+
+    pub fn contains(&self, handle: &Handle<T>) -> bool {
+        self.assets.contains_key(&handle.id)
+    }
+
+    /// `None` for handles removed from or never returned by this `Assets`
+    pub fn load_state(&self, handle: &Handle<T>) -> Option<&LoadState> {
+        self.states.get(&handle.id)
+    }
+
+    /// Path the asset was requested from, if any
+    pub fn path(&self, handle: &Handle<T>) -> Option<&Path> {
+        self.paths.get(&handle.id).map(PathBuf::as_path)
+    }
+
+    /// Every asset requested from a path, loaded or not
+    pub fn paths(&self) -> impl Iterator<Item = (Handle<T>, &Path)> {
+        self.paths
+            .iter()
+            .map(|(id, path)| (Handle::new(*id), path.as_path()))
+    }
+
+    /// Requests the file of `handle` again, the current asset stays available until the new one
+    /// replaces it.
+    ///
+    /// Returns `false` if the asset was not requested from a path.
+    pub fn reload(&mut self, handle: &Handle<T>) -> bool {
+        match self.paths.get(&handle.id) {
+            Some(path) => {
+                let path = path.clone();
+                self.request(*handle, AssetRequest::Path(path));
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn remove(&mut self, handle: &Handle<T>) -> Option<T> {
+        self.states.remove(&handle.id);
+        self.requests.retain(|(request, _)| request != handle);
+        if let Some(path) = self.paths.remove(&handle.id) {
+            self.handles_by_path.remove(&path);
+        }
+        self.assets.remove(&handle.id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Handle<T>, &T)> {
+        self.assets
+            .iter()
+            .map(|(id, asset)| (Handle::new(*id), asset))
+    }
+
+    /// Whether requests are waiting for the `AssetLoading` stage
+    pub fn has_requests(&self) -> bool {
+        !self.requests.is_empty()
+    }
+
+    pub(crate) fn take_requests(&mut self) -> Vec<(Handle<T>, AssetRequest<T>)> {
+        std::mem::take(&mut self.requests)
+    }
+
+    /// Stores the result of a request, returns the asset replaced by a reload
+    pub(crate) fn finish(&mut self, handle: Handle<T>, result: Result<T, String>) -> Option<T> {
+        match result {
+            Ok(asset) => {
+                self.states.insert(handle.id, LoadState::Loaded);
+                self.assets.insert(handle.id, asset)
+            }
+            Err(error) => {
+                self.states.insert(handle.id, LoadState::Failed(error));
+                None
+            }
+        }
+    }
+
+    fn reserve(&mut self) -> Handle<T> {
+        let handle = Handle::new(self.next_id);
+        self.next_id += 1;
+        handle
+    }
+
+    fn request(&mut self, handle: Handle<T>, request: AssetRequest<T>) {
+        self.states.insert(handle.id, LoadState::Loading);
+        self.requests.push((handle, request));
+    }
+}
+
+/// Fulfills every pending request of `assets`, loading paths with `load_path` which is given
+/// the asset a reload replaces
+fn process_requests<T>(
+    assets: &mut Assets<T>,
+    ctx: &mut miniquad::Context,
+    mut load_path: impl FnMut(&mut miniquad::Context, &Path, Option<&T>) -> Result<T, String>,
+) {
+    for (handle, request) in assets.take_requests() {
+        let result = match request {
+            AssetRequest::Path(path) => load_path(ctx, &path, assets.get(&handle)),
+            AssetRequest::Deferred(create) => create(ctx),
+        };
+        assets.finish(handle, result);
+    }
+}
+
+/// Reloads write into the texture they replace, so copies of its `miniquad::Texture` stay
+/// valid. An image changing format gets a new texture and the previous one is kept alive.
+pub fn load_textures(
+    mut ctx: ResMut<miniquad::Context>,
+    mut textures: ResMut<Assets<SimpleMeshTexture>>,
+) {
+    process_requests(&mut textures, &mut ctx, |ctx, path, current| {
+        let image = Image::load(path).map_err(|error| error.to_string())?;
+        Ok(match current {
+            Some(current) if current.0.format == image.format => {
+                let mut texture = current.0;
+                texture.resize(ctx, image.width, image.height, Some(&image.bytes));
+                SimpleMeshTexture(texture)
+            }
+            _ => SimpleMeshTexture::from_image(ctx, &image, miniquad::FilterMode::Linear),
+        })
+    });
+}
+
+pub fn load_meshes(mut ctx: ResMut<miniquad::Context>, mut meshes: ResMut<Assets<SimpleMesh>>) {
+    process_requests(&mut meshes, &mut ctx, |_, path, _| {
+        Err(format!(
+            "{}: meshes can not be loaded from a file, use `add_deferred`",
+            path.display()
+        ))
+    });
+}
+
+pub fn load_fonts(
+    mut ctx: ResMut<miniquad::Context>,
+    mut fonts: ResMut<Fonts>,
+    mut assets: ResMut<Assets<Font>>,
+) {
+    process_requests(&mut assets, &mut ctx, |_, path, _| {
+        fonts.load(path).map_err(|error| error.to_string())
+    });
+}
+
+pub fn load_shaders(mut ctx: ResMut<miniquad::Context>, mut shaders: ResMut<Assets<ShaderSource>>) {
+    process_requests(&mut shaders, &mut ctx, |_, path, _| {
+        let source = std::fs::read_to_string(path)
+            .map_err(|error| format!("{}: {}", path.display(), error))?;
+        ShaderSource::parse(&source).map_err(|error| format!("{}: {}", path.display(), error))
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_path_returns_the_same_handle() {
+        let mut assets = Assets::<u32>::default();
+        let first = assets.load("sprites/ship.png");
+        let second = assets.load("sprites/ship.png");
+        let other = assets.load("sprites/rock.png");
+
+        assert_eq!(first, second);
+        assert_ne!(first, other);
+        assert_eq!(assets.take_requests().len(), 2);
+        assert_eq!(assets.load_state(&first), Some(&LoadState::Loading));
+        assert_eq!(assets.get(&first), None);
+    }
+
+    #[test]
+    fn finished_requests_fill_the_handle() {
+        let mut assets = Assets::<u32>::default();
+        let loaded = assets.load("a");
+        let failed = assets.load("b");
+        assets.finish(loaded, Ok(7));
+        assets.finish(failed, Err("missing".to_string()));
+
+        assert_eq!(assets.get(&loaded), Some(&7));
+        assert_eq!(assets.load_state(&loaded), Some(&LoadState::Loaded));
+        assert_eq!(
+            assets.load_state(&failed),
+            Some(&LoadState::Failed("missing".to_string()))
+        );
+    }
+
+    #[test]
+    fn failed_paths_are_requested_again() {
+        let mut assets = Assets::<u32>::default();
+        let handle = assets.load("a");
+        assets.take_requests();
+        assets.finish(handle, Err("missing".to_string()));
+
+        assert_eq!(assets.load("a"), handle);
+        assert_eq!(assets.load_state(&handle), Some(&LoadState::Loading));
+        assert!(assets.has_requests());
+    }
+
+    #[test]
+    fn reloads_keep_the_asset_until_replaced() {
+        let mut assets = Assets::<u32>::default();
+        let handle = assets.load("a");
+        assets.take_requests();
+        assets.finish(handle, Ok(1));
+
+        assert!(assets.reload(&handle));
+        assert_eq!(assets.load_state(&handle), Some(&LoadState::Loading));
+        assert_eq!(assets.get(&handle), Some(&1));
+
+        assets.take_requests();
+        assert_eq!(assets.finish(handle, Err("invalid".to_string())), None);
+        assert_eq!(assets.get(&handle), Some(&1));
+        assert_eq!(assets.finish(handle, Ok(2)), Some(1));
+
+        let added = assets.add(3);
+        assert!(!assets.reload(&added));
+    }
+
+    #[test]
+    fn removed_handles_are_forgotten() {
+        let mut assets = Assets::<u32>::default();
+        let handle = assets.add(3);
+        assert_eq!(assets.remove(&handle), Some(3));
+        assert_eq!(assets.load_state(&handle), None);
+        assert!(!assets.contains(&handle));
+    }
+}
@@ -0,0 +1,433 @@
+use crate::prelude::*;
+
+use super::{
+    components::Projection,
+    layer::{RenderLayer, RenderLayers},
+    sprite::Rect,
+};
+
+/// Fills the viewport of cameras before they draw, unless their `Camera` overrides it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClearColor(pub Color);
+
+impl Default for ClearColor {
+    fn default() -> Self {
+        Self(Color::rgb(0.13, 0.137, 0.137))
+    }
+}
+
+impl ClearColor {
+    /// The color as stored in the default framebuffer, which holds sRGB encoded values, so
+    /// linear colors are encoded first
+    pub fn framebuffer_rgba(color: Color) -> (f32, f32, f32, f32) {
+        let [r, g, b, a] = color.as_rgba_f32();
+        (r, g, b, a)
+    }
+}
+
+/// How a camera fills its viewport before drawing
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ClearColorConfig {
+    /// With the `ClearColor` resource
+    #[default]
+    Default,
+    Custom(Color),
+    /// Keeps what the cameras of lower priority drew
+    None,
+}
+
+/// Where and in which order an entity with a `Projection` and a `Transform` is drawn, cameras
+/// without it fill the window
+#[derive(Debug, Clone, Copy, Component)]
+pub struct Camera {
+    /// Part of the window drawn to, as fractions of its size from the bottom left corner
+    pub viewport: Rect,
+    pub clear_color: ClearColorConfig,
+    /// Cameras are drawn from the lowest priority to the highest
+    pub priority: i32,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self {
+            viewport: Rect::new(0.0, 0.0, 1.0, 1.0),
+            clear_color: ClearColorConfig::Default,
+            priority: 0,
+        }
+    }
+}
+
+impl Camera {
+    /// Draws over the cameras of lower priority without clearing, e.g. for a HUD
+    pub fn overlay(priority: i32) -> Self {
+        Self {
+            clear_color: ClearColorConfig::None,
+            priority,
+            ..Default::default()
+        }
+    }
+
+    /// The viewport in pixels as `(x, y, width, height)`, from the bottom left corner
+    pub fn pixel_viewport(&self, window: Window) -> (i32, i32, i32, i32) {
+        let size = Vec2::new(window.width, window.height);
+        let min = (self.viewport.min * size).round();
+        let max = (self.viewport.max * size).round();
+        (
+            min.x as i32,
+            min.y as i32,
+            (max.x - min.x).max(0.0) as i32,
+            (max.y - min.y).max(0.0) as i32,
+        )
+    }
+}
+
+/// Half line through the world, e.g. everything under the mouse
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ray {
+    pub origin: Vec3,
+    /// Normalized
+    pub direction: Vec3,
+}
+
+impl Ray {
+    pub fn at(&self, distance: f32) -> Vec3 {
+        self.origin + self.direction * distance
+    }
+
+    /// Where the ray crosses the plane at height `z`, `None` when it runs parallel to it
+    pub fn intersect_z(&self, z: f32) -> Option<Vec3> {
+        if self.direction.z.abs() <= f32::EPSILON {
+            return None;
+        }
+        let distance = (z - self.origin.z) / self.direction.z;
+        (distance >= 0.0).then_some(self.at(distance))
+    }
+}
+
+/// A camera ready to draw with
+#[derive(Debug, Clone, Copy)]
+pub struct CameraView {
+    pub entity: Entity,
+    pub view_projection: Mat4,
+    /// In pixels as `(x, y, width, height)`, from the bottom left corner
+    pub viewport: (i32, i32, i32, i32),
+    /// Resolved against the `ClearColor` resource
+    pub clear_color: Option<Color>,
+    pub layers: RenderLayers,
+    pub priority: i32,
+    /// The window the viewport is part of
+    pub window: Window,
+    /// `screen_to_world` of `Mouse::pos` when the mouse is over the viewport, updated every frame
+    pub mouse_world_pos: Option<Vec2>,
+}
+
+impl CameraView {
+    pub fn sees(&self, layer: RenderLayer) -> bool {
+        self.layers.contains(layer)
+    }
+
+    /// Whether a position in window pixels from the top left corner, like `Mouse::pos`, is in
+    /// the viewport
+    pub fn contains(&self, screen: Vec2) -> bool {
+        let (x, y, width, height) = self.viewport;
+        let pixel = Vec2::new(screen.x, self.window.height - screen.y);
+        pixel.x >= x as f32
+            && pixel.y >= y as f32
+            && pixel.x < (x + width) as f32
+            && pixel.y < (y + height) as f32
+    }
+
+    /// Ray from the near plane through a position in window pixels from the top left corner,
+    /// `None` for an empty viewport
+    pub fn viewport_to_ray(&self, screen: Vec2) -> Option<Ray> {
+        let (x, y, width, height) = self.viewport;
+        if width <= 0 || height <= 0 {
+            return None;
+        }
+        let pixel = Vec2::new(screen.x, self.window.height - screen.y);
+        let ndc = (pixel - Vec2::new(x as f32, y as f32)) / Vec2::new(width as f32, height as f32)
+            * 2.0
+            - Vec2::ONE;
+        let inverse = self.view_projection.inverse();
+        let near = inverse.project_point3(ndc.extend(-1.0));
+        let far = inverse.project_point3(ndc.extend(1.0));
+        Some(Ray {
+            origin: near,
+            direction: (far - near).try_normalize()?,
+        })
+    }
+
+    /// Point of the z = 0 plane under a position in window pixels from the top left corner
+    pub fn screen_to_world(&self, screen: Vec2) -> Option<Vec2> {
+        self.viewport_to_ray(screen)?
+            .intersect_z(0.0)
+            .map(Vec3::truncate)
+    }
+
+    /// Position in window pixels from the top left corner a world point is drawn at, `None` when
+    /// it is behind the camera
+    pub fn world_to_screen(&self, world: Vec3) -> Option<Vec2> {
+        let clip = self.view_projection * world.extend(1.0);
+        if clip.w <= 0.0 {
+            return None;
+        }
+        let ndc = clip.truncate().truncate() / clip.w;
+        let (x, y, width, height) = self.viewport;
+        let pixel = (ndc + Vec2::ONE) / 2.0 * Vec2::new(width as f32, height as f32)
+            + Vec2::new(x as f32, y as f32);
+        Some(Vec2::new(pixel.x, self.window.height - pixel.y))
+    }
+
+    fn locate_mouse(&mut self, mouse: &Mouse) {
+        self.mouse_world_pos = if self.contains(mouse.pos) {
+            self.screen_to_world(mouse.pos)
+        } else {
+            None
+        };
+    }
+
+    /// Starts a pass on the default framebuffer restricted to the viewport
+    pub(crate) fn begin_pass(&self, ctx: &mut miniquad::Context) {
+        let (x, y, width, height) = self.viewport;
+        ctx.begin_default_pass(miniquad::PassAction::Nothing);
+        ctx.apply_viewport(x, y, width, height);
+        ctx.apply_scissor_rect(x, y, width, height);
+    }
+
+    /// Fills the viewport with the clear color, if any, during a pass started by `begin_pass`
+    pub(crate) fn clear(&self, ctx: &mut miniquad::Context) {
+        if let Some(color) = self.clear_color {
+            ctx.clear(Some(ClearColor::framebuffer_rgba(color)), None, None);
+        }
+    }
+}
+
+/// Every camera of the frame, gathered by `gather_cameras`
+#[derive(Debug, Default)]
+pub struct Cameras {
+    views: Vec<CameraView>,
+}
+
+impl Cameras {
+    /// From the lowest priority to the highest, cameras of equal priority are ordered by entity
+    pub fn iter(&self) -> impl Iterator<Item = &CameraView> {
+        self.views.iter()
+    }
+
+    pub fn get(&self, entity: Entity) -> Option<&CameraView> {
+        self.views.iter().find(|view| view.entity == entity)
+    }
+
+    /// The camera of highest priority under the mouse
+    pub fn under_mouse(&self) -> Option<&CameraView> {
+        self.views
+            .iter()
+            .rev()
+            .find(|view| view.mouse_world_pos.is_some())
+    }
+
+    pub fn len(&self) -> usize {
+        self.views.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.views.is_empty()
+    }
+}
+
+/// Projection of a `viewport` pixels large viewport times the inverse of the camera transform
+pub(crate) fn view_projection(
+    projection: &Projection,
+    transform: &Transform,
+    viewport: Vec2,
+) -> Mat4 {
+    projection.matrix(viewport) * transform.compute_matrix().inverse()
+}
+
+type CameraComponents<'a> = (
+    Entity,
+    &'a Projection,
+    &'a Transform,
+    Option<&'a Camera>,
+    Option<&'a RenderLayers>,
+);
+
+/// Gathers the `Cameras` from their components. Runs during `InputProcessing`, so
+/// `CameraView::mouse_world_pos` maps the mouse of this frame through the cameras as they are
+/// now, and again at the start of the `RenderStage` to draw the cameras as they end the frame.
+pub fn gather_cameras(
+    frame_input: Res<FrameInput>,
+    clear_color: Res<ClearColor>,
+    query: Query<CameraComponents>,
+    mut cameras: ResMut<Cameras>,
+) {
+    cameras.views.clear();
+    cameras.views.extend(
+        query
+            .iter()
+            .map(|(entity, projection, transform, camera, layers)| {
+                let camera = camera.copied().unwrap_or_default();
+                let viewport = camera.pixel_viewport(frame_input.window);
+                let size = Vec2::new(viewport.2 as f32, viewport.3 as f32);
+                let mut view = CameraView {
+                    entity,
+                    view_projection: view_projection(projection, transform, size),
+                    viewport,
+                    clear_color: match camera.clear_color {
+                        ClearColorConfig::Default => Some(clear_color.0),
+                        ClearColorConfig::Custom(color) => Some(color),
+                        ClearColorConfig::None => None,
+                    },
+                    layers: layers.copied().unwrap_or_default(),
+                    priority: camera.priority,
+                    window: frame_input.window,
+                    mouse_world_pos: None,
+                };
+                view.locate_mouse(&frame_input.mouse);
+                view
+            }),
+    );
+    cameras
+        .views
+        .sort_by_key(|view| (view.priority, view.entity));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mq::components::ScalingMode;
+
+    #[test]
+    fn viewport_is_scaled_to_the_window() {
+        let camera = Camera {
+            viewport: Rect::new(0.5, 0.0, 0.5, 1.0),
+            ..Default::default()
+        };
+        let window = Window {
+            width: 800.0,
+            height: 600.0,
+        };
+        assert_eq!(camera.pixel_viewport(window), (400, 0, 400, 600));
+    }
+
+    #[test]
+    fn cameras_are_sorted_by_priority() {
+        let mut world = World::default();
+        world.insert_resource(FrameInput::default());
+        world.insert_resource(Cameras::default());
+        world.insert_resource(ClearColor(Color::BLACK));
+        let hud = world
+            .spawn()
+            .insert_bundle((Projection::default(), Transform::identity()))
+            .insert(Camera::overlay(1))
+            .id();
+        let main = world
+            .spawn()
+            .insert_bundle((Projection::default(), Transform::identity()))
+            .id();
+
+        SystemStage::single_threaded()
+            .with_system(gather_cameras)
+            .run(&mut world);
+
+        let cameras = world.get_resource::<Cameras>().unwrap();
+        let order: Vec<_> = cameras.iter().map(|view| view.entity).collect();
+        assert_eq!(order, vec![main, hud]);
+        assert_eq!(cameras.get(hud).unwrap().clear_color, None);
+        assert_eq!(cameras.get(main).unwrap().clear_color, Some(Color::BLACK));
+    }
+
+    #[test]
+    fn mouse_is_located_with_the_cameras_of_this_frame() {
+        let mut world = World::default();
+        let mut frame_input = FrameInput {
+            window: Window {
+                width: 800.0,
+                height: 600.0,
+            },
+            ..Default::default()
+        };
+        frame_input.mouse.pos = Vec2::new(400.0, 300.0);
+        world.insert_resource(frame_input);
+        world.insert_resource(Cameras::default());
+        world.insert_resource(ClearColor::default());
+        let camera = world
+            .spawn()
+            .insert_bundle((Projection::default(), Transform::identity()))
+            .id();
+        SystemStage::single_threaded()
+            .with_system(gather_cameras)
+            .run(&mut world);
+
+        world.get_mut::<Transform>(camera).unwrap().translation = Vec3::new(10.0, 0.0, 0.0);
+        SystemStage::single_threaded()
+            .with_system(gather_cameras)
+            .run(&mut world);
+
+        let cameras = world.get_resource::<Cameras>().unwrap();
+        let mouse = cameras.under_mouse().unwrap().mouse_world_pos.unwrap();
+        assert!((mouse - Vec2::new(10.0, 0.0)).length() < 1e-4);
+    }
+
+    #[test]
+    fn linear_clear_colors_are_srgb_encoded() {
+        let (r, g, b, a) = ClearColor::framebuffer_rgba(Color::rgba_linear(0.2, 0.0, 1.0, 0.5));
+        assert!((r - 0.4845).abs() < 1e-4);
+        assert_eq!((g, a), (0.0, 0.5));
+        assert!((b - 1.0).abs() < 1e-6);
+
+        let (r, g, b, _) = ClearColor::framebuffer_rgba(Color::rgb(0.13, 0.137, 0.137));
+        assert!((r - 0.13).abs() < 1e-5);
+        assert!((g - 0.137).abs() < 1e-5 && (b - 0.137).abs() < 1e-5);
+    }
+
+    fn view(projection: Projection, transform: Transform, camera: Camera) -> CameraView {
+        let window = Window {
+            width: 800.0,
+            height: 600.0,
+        };
+        let viewport = camera.pixel_viewport(window);
+        let size = Vec2::new(viewport.2 as f32, viewport.3 as f32);
+        CameraView {
+            entity: Entity::from_raw(0),
+            view_projection: view_projection(&projection, &transform, size),
+            viewport,
+            clear_color: None,
+            layers: RenderLayers::default(),
+            priority: 0,
+            window,
+            mouse_world_pos: None,
+        }
+    }
+
+    #[test]
+    fn screen_positions_map_to_the_world_and_back() {
+        let camera = Camera {
+            viewport: Rect::new(0.5, 0.0, 0.5, 1.0),
+            ..Default::default()
+        };
+        let projection = Projection::Orthographic(ScalingMode::FixedHeight(6.0));
+        let view = view(projection, Transform::from_xyz(10.0, 0.0, 0.0), camera);
+
+        let top_right = view.screen_to_world(Vec2::new(800.0, 0.0)).unwrap();
+        assert!(top_right.abs_diff_eq(Vec2::new(12.0, 3.0), 1e-4));
+        let center = view.world_to_screen(Vec3::new(10.0, 0.0, 5.0)).unwrap();
+        assert!(center.abs_diff_eq(Vec2::new(600.0, 300.0), 1e-3));
+        assert!(!view.contains(Vec2::new(200.0, 300.0)));
+    }
+
+    #[test]
+    fn perspective_rays_start_at_the_camera() {
+        let projection = Projection::Perspective { fov_y: 90.0 };
+        let transform = Transform::from_xyz(0.0, 0.0, 10.0);
+        let view = view(projection, transform, Camera::default());
+
+        let ray = view.viewport_to_ray(Vec2::new(400.0, 300.0)).unwrap();
+        assert!(ray.direction.abs_diff_eq(-Vec3::Z, 1e-4));
+        assert!((ray.origin.z - (10.0 - Z_NEAR)).abs() < 1e-3);
+        // The top edge of a 90 degree view is as far above the center as it is deep
+        let top = view.screen_to_world(Vec2::new(400.0, 0.0)).unwrap();
+        assert!(top.abs_diff_eq(Vec2::new(0.0, 10.0), 1e-2));
+        assert!(view.world_to_screen(Vec3::new(0.0, 0.0, 20.0)).is_none());
+    }
+}
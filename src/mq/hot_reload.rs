@@ -0,0 +1,164 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use bevy_app::{App, CoreStage, Plugin};
+use bevy_utils::tracing::error;
+
+use crate::prelude::*;
+
+use super::{
+    components::SimpleMeshTexture,
+    shaders::{quad::QuadPipeline, ShaderSource},
+    RenderStage, RenderSystem,
+};
+
+/// Settings of the `HotReloadPlugin`
+#[derive(Debug, Clone)]
+pub struct HotReload {
+    /// Seconds between two checks of the watched files
+    pub poll_interval: f64,
+    /// `ShaderSource` file compiled into the `QuadPipeline` in place of `shaders::quad::VERTEX`
+    /// and `FRAGMENT`
+    pub quad_shader: Option<PathBuf>,
+    /// Why the quad shader last failed to compile, the previous pipeline is drawn with meanwhile
+    pub quad_shader_error: Option<String>,
+}
+
+impl Default for HotReload {
+    fn default() -> Self {
+        Self {
+            poll_interval: 0.5,
+            quad_shader: None,
+            quad_shader_error: None,
+        }
+    }
+}
+
+/// Reloads the textures and shaders loaded from a path when their file changes on disk, add it
+/// after the `MiniquadPlugin`
+#[derive(Default)]
+pub struct HotReloadPlugin;
+
+impl Plugin for HotReloadPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<HotReload>()
+            .add_system_to_stage(CoreStage::PostUpdate, watch_assets::<SimpleMeshTexture>)
+            .add_system_to_stage(CoreStage::PostUpdate, watch_assets::<ShaderSource>)
+            .add_system_to_stage(RenderStage, reload_quad_shader.before(RenderSystem::Stats));
+    }
+}
+
+/// Modification times of the files seen so far, `None` while a file is missing
+#[derive(Debug, Default)]
+pub(crate) struct FileWatcher {
+    modified: HashMap<PathBuf, Option<SystemTime>>,
+}
+
+impl FileWatcher {
+    /// Whether `path` was written or created since the last check, the first check of a path
+    /// only records its state
+    pub fn changed(&mut self, path: &Path) -> bool {
+        let modified = std::fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok();
+        match self.modified.insert(path.to_path_buf(), modified) {
+            Some(previous) => modified.is_some() && previous != modified,
+            None => false,
+        }
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct WatchState {
+    watcher: FileWatcher,
+    last_poll: f64,
+}
+
+/// Requests again every asset of `Assets<T>` whose file changed
+pub fn watch_assets<T: Send + Sync + 'static>(
+    frame_input: Res<FrameInput>,
+    hot_reload: Res<HotReload>,
+    mut state: Local<WatchState>,
+    mut assets: ResMut<Assets<T>>,
+) {
+    let now = frame_input.time.time_in_seconds_since_start;
+    if now - state.last_poll < hot_reload.poll_interval {
+        return;
+    }
+    state.last_poll = now;
+
+    let changed: Vec<_> = assets
+        .paths()
+        .filter(|(_, path)| state.watcher.changed(path))
+        .map(|(handle, _)| handle)
+        .collect();
+    for handle in changed {
+        assets.reload(&handle);
+    }
+}
+
+/// Rebuilds the `QuadPipeline` whenever the `HotReload::quad_shader` source changes
+pub fn reload_quad_shader(
+    mut hot_reload: ResMut<HotReload>,
+    mut requested: Local<Option<(PathBuf, Handle<ShaderSource>)>>,
+    mut applied: Local<Option<ShaderSource>>,
+    mut shaders: ResMut<Assets<ShaderSource>>,
+    mut ctx: ResMut<miniquad::Context>,
+    mut pipeline: ResMut<QuadPipeline>,
+) {
+    let path = match &hot_reload.quad_shader {
+        Some(path) => path.clone(),
+        None => return,
+    };
+    let handle = match requested.as_ref() {
+        Some((requested_path, handle)) if *requested_path == path => *handle,
+        _ => {
+            let handle = shaders.load(&path);
+            *requested = Some((path.clone(), handle));
+            handle
+        }
+    };
+
+    let source = match shaders.get(&handle) {
+        Some(source) if applied.as_ref() != Some(source) => source,
+        _ => return,
+    };
+    *applied = Some(source.clone());
+    match pipeline.reload(&mut ctx, source) {
+        Ok(()) => hot_reload.quad_shader_error = None,
+        Err(error) => {
+            let error = format!("{}: {}", path.display(), error);
+            error!("failed to reload the quad shader: {}", error);
+            hot_reload.quad_shader_error = Some(error);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::File, time::Duration};
+
+    use super::*;
+
+    #[test]
+    fn watcher_reports_written_and_created_files() {
+        let path = std::env::temp_dir().join(format!("hot_reload_{}.png", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut watcher = FileWatcher::default();
+        assert!(!watcher.changed(&path));
+
+        let file = File::create(&path).unwrap();
+        assert!(watcher.changed(&path));
+        assert!(!watcher.changed(&path));
+
+        file.set_modified(SystemTime::now() + Duration::from_secs(10))
+            .unwrap();
+        assert!(watcher.changed(&path));
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(!watcher.changed(&path));
+    }
+}
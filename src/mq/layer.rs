@@ -0,0 +1,129 @@
+use std::cmp::Ordering;
+
+use crate::prelude::*;
+
+/// Draw order of an entity among the entities of its pipeline, lower layers are drawn first, so
+/// higher ones end up on top. Entities without the component are on layer 0.
+///
+/// Within a layer, opaque entities are drawn before transparent ones, each back to front.
+///
+/// Layers only order entities of the same pipeline: `Material` entities are drawn after every
+/// quad, and the `DebugShape2D` after both, by the cameras that see layer 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Component)]
+pub struct RenderLayer(pub u8);
+
+impl RenderLayer {
+    /// Number of layers a `RenderLayers` mask can hold
+    pub const TOTAL: u8 = 32;
+}
+
+/// Layers a camera draws, every layer when the camera has no such component
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Component)]
+pub struct RenderLayers(u32);
+
+impl Default for RenderLayers {
+    fn default() -> Self {
+        Self::all()
+    }
+}
+
+impl RenderLayers {
+    pub const fn all() -> Self {
+        Self(u32::MAX)
+    }
+
+    pub const fn none() -> Self {
+        Self(0)
+    }
+
+    /// Only `layer`, layers past `RenderLayer::TOTAL` are ignored
+    pub fn layer(layer: u8) -> Self {
+        Self::none().with(layer)
+    }
+
+    pub fn with(self, layer: u8) -> Self {
+        Self(self.0 | Self::bit(layer))
+    }
+
+    pub fn without(self, layer: u8) -> Self {
+        Self(self.0 & !Self::bit(layer))
+    }
+
+    pub fn contains(&self, layer: RenderLayer) -> bool {
+        self.0 & Self::bit(layer.0) != 0
+    }
+
+    fn bit(layer: u8) -> u32 {
+        1u32.checked_shl(layer as u32).unwrap_or(0)
+    }
+}
+
+/// Sort key of a draw, ties are broken by the caller
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct DrawOrder {
+    pub layer: RenderLayer,
+    pub transparent: bool,
+    /// World z, cameras look down -Z so the farthest draws have the lowest
+    pub depth: f32,
+}
+
+impl DrawOrder {
+    pub fn new(layer: Option<&RenderLayer>, transparent: bool, model: &Mat4) -> Self {
+        Self {
+            layer: layer.copied().unwrap_or_default(),
+            transparent,
+            depth: model.w_axis.z,
+        }
+    }
+
+    pub fn compare(&self, other: &Self) -> Ordering {
+        self.layer
+            .cmp(&other.layer)
+            .then(self.transparent.cmp(&other.transparent))
+            .then(self.depth.total_cmp(&other.depth))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn order(layer: u8, transparent: bool, depth: f32) -> DrawOrder {
+        DrawOrder {
+            layer: RenderLayer(layer),
+            transparent,
+            depth,
+        }
+    }
+
+    #[test]
+    fn draws_are_sorted_by_layer_then_depth() {
+        let mut draws = vec![
+            order(1, false, -5.0),
+            order(0, true, -3.0),
+            order(0, false, 2.0),
+            order(0, true, -4.0),
+            order(0, false, 1.0),
+        ];
+        draws.sort_by(DrawOrder::compare);
+        assert_eq!(
+            draws,
+            vec![
+                order(0, false, 1.0),
+                order(0, false, 2.0),
+                order(0, true, -4.0),
+                order(0, true, -3.0),
+                order(1, false, -5.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn layer_masks() {
+        let layers = RenderLayers::layer(2).with(5).without(2);
+        assert!(layers.contains(RenderLayer(5)));
+        assert!(!layers.contains(RenderLayer(2)));
+        assert!(RenderLayers::default().contains(RenderLayer(31)));
+        assert_eq!(RenderLayers::layer(40), RenderLayers::none());
+    }
+}
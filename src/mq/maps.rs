@@ -1,6 +1,24 @@
-use miniquad::KeyCode;
+use miniquad::{KeyCode, KeyMods, TouchPhase};
 
-use crate::input::KeyboardKey;
+use crate::input::{self, KeyboardKey, Modifiers};
+
+pub fn map_to_touch_phase(phase: TouchPhase) -> input::TouchPhase {
+    match phase {
+        TouchPhase::Started => input::TouchPhase::Started,
+        TouchPhase::Moved => input::TouchPhase::Moved,
+        TouchPhase::Ended => input::TouchPhase::Ended,
+        TouchPhase::Cancelled => input::TouchPhase::Cancelled,
+    }
+}
+
+pub fn map_to_modifiers(keymods: KeyMods) -> Modifiers {
+    Modifiers {
+        shift: keymods.shift,
+        ctrl: keymods.ctrl,
+        alt: keymods.alt,
+        logo: keymods.logo,
+    }
+}
 
 pub fn map_to_keyboard_key(keycode: KeyCode) -> Option<KeyboardKey> {
     match keycode {
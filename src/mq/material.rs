@@ -0,0 +1,279 @@
+use std::{any::TypeId, collections::HashMap, marker::PhantomData};
+
+use bevy_app::{App, CoreStage, Plugin};
+use bevy_ecs::{component::TableStorage, system::SystemState};
+use miniquad::{
+    BlendState, BufferLayout, Pipeline, PipelineParams, PrimitiveType, Shader, ShaderMeta, Texture,
+    VertexAttribute, VertexFormat,
+};
+
+use crate::prelude::*;
+
+use super::{
+    camera::CameraView,
+    components::{MeshColor, SimpleMesh},
+    layer::{DrawOrder, RenderLayer},
+    shaders::ShaderSource,
+    systems::{CameraPass, CameraPasses},
+    Assets, Handle, RenderPass, RenderStats,
+};
+
+/// Shaders and uniforms of a custom pipeline. Entities with a `SimpleMesh` and the material
+/// component are drawn with it instead of the quad pipeline once its `MaterialPlugin` is added.
+///
+/// Material entities are drawn after every quad of the camera, so they always end up over quads.
+pub trait Material: Component {
+    /// `#[repr(C)]` struct laid out as the uniforms of `meta`
+    type Uniform;
+
+    /// Sources reading the `position` and `uv` attributes of `shaders::Vertex`, the instanced
+    /// stage is ignored
+    fn shader() -> ShaderSource;
+
+    fn meta() -> ShaderMeta;
+
+    /// Blending is disabled when `None`
+    fn blend_state() -> Option<BlendState> {
+        None
+    }
+
+    fn uniform(&self, model: Mat4, projection: Mat4, color: Option<&MeshColor>) -> Self::Uniform;
+
+    /// Bound to the images of `meta`, in order
+    fn textures(&self) -> Vec<Texture> {
+        Vec::new()
+    }
+}
+
+/// Draws the entities with a material `M`
+pub struct MaterialPlugin<M>(PhantomData<fn() -> M>);
+
+impl<M> Default for MaterialPlugin<M> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<M: Material> Plugin for MaterialPlugin<M> {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MaterialPipelines>()
+            .add_system_to_stage(CoreStage::PostUpdate, mark_materials::<M>);
+        app.world
+            .get_resource_or_insert_with(CameraPasses::default)
+            .add(RenderPass::Materials, MaterialPass::<M>::default());
+    }
+}
+
+/// Set on entities drawn by a `MaterialPlugin`, the quad pipeline skips them
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct WithMaterial;
+
+impl Component for WithMaterial {
+    type Storage = TableStorage;
+}
+
+/// Keeps `WithMaterial` in sync with the `M` components
+fn mark_materials<M: Material>(
+    mut commands: Commands,
+    added: Query<Entity, (With<M>, Without<WithMaterial>)>,
+    removed: RemovedComponents<M>,
+) {
+    for entity in added.iter() {
+        commands.entity(entity).insert(WithMaterial);
+    }
+    for entity in removed.iter() {
+        commands.entity(entity).remove::<WithMaterial>();
+    }
+}
+
+struct MaterialPipeline {
+    triangles: Pipeline,
+    lines: Pipeline,
+}
+
+/// Pipelines of every material type, built the first time one of its entities is drawn.
+///
+/// A material whose shader fails to compile is not built again, its entities are skipped.
+#[derive(Default)]
+pub struct MaterialPipelines {
+    pipelines: HashMap<TypeId, Result<MaterialPipeline, String>>,
+}
+
+impl MaterialPipelines {
+    /// Why the pipeline of `M` could not be built
+    pub fn error<M: Material>(&self) -> Option<&str> {
+        match self.pipelines.get(&TypeId::of::<M>()) {
+            Some(Err(error)) => Some(error),
+            _ => None,
+        }
+    }
+
+    /// Number of material types built, including the failed ones
+    pub fn len(&self) -> usize {
+        self.pipelines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pipelines.is_empty()
+    }
+
+    fn get_or_build<M: Material>(
+        &mut self,
+        ctx: &mut miniquad::Context,
+        primitive_type: PrimitiveType,
+    ) -> Option<&Pipeline> {
+        let pipeline = self
+            .pipelines
+            .entry(TypeId::of::<M>())
+            .or_insert_with(|| {
+                build::<M>(ctx)
+                    .map_err(|error| format!("{}: {}", std::any::type_name::<M>(), error))
+            })
+            .as_ref()
+            .ok()?;
+        Some(match primitive_type {
+            PrimitiveType::Triangles => &pipeline.triangles,
+            PrimitiveType::Lines => &pipeline.lines,
+        })
+    }
+}
+
+fn build<M: Material>(
+    ctx: &mut miniquad::Context,
+) -> Result<MaterialPipeline, miniquad::ShaderError> {
+    let source = M::shader();
+    let shader = Shader::new(ctx, &source.vertex, &source.fragment, M::meta())?;
+    let mut pipeline = |primitive_type| {
+        Pipeline::with_params(
+            ctx,
+            &[BufferLayout::default()],
+            &[
+                VertexAttribute::new("position", VertexFormat::Float3),
+                VertexAttribute::new("uv", VertexFormat::Float2),
+            ],
+            shader,
+            PipelineParams {
+                primitive_type,
+                color_blend: M::blend_state(),
+                ..Default::default()
+            },
+        )
+    };
+    Ok(MaterialPipeline {
+        triangles: pipeline(PrimitiveType::Triangles),
+        lines: pipeline(PrimitiveType::Lines),
+    })
+}
+
+type MaterialComponents<'a, M> = (
+    Entity,
+    &'a M,
+    Option<&'a SimpleMesh>,
+    Option<&'a Handle<SimpleMesh>>,
+    &'a Transform,
+    Option<&'a MeshColor>,
+    Option<&'a RenderLayer>,
+);
+
+type MaterialParams<M> = (
+    ResMut<'static, miniquad::Context>,
+    Query<'static, 'static, MaterialComponents<'static, M>>,
+    Res<'static, Assets<SimpleMesh>>,
+    ResMut<'static, MaterialPipelines>,
+    ResMut<'static, RenderStats>,
+);
+
+/// Draws every entity with a material `M`, a draw call each, sorted like the quad pass
+struct MaterialPass<M: Material> {
+    state: Option<SystemState<MaterialParams<M>>>,
+}
+
+impl<M: Material> Default for MaterialPass<M> {
+    fn default() -> Self {
+        Self { state: None }
+    }
+}
+
+impl<M: Material> CameraPass for MaterialPass<M> {
+    fn draw(&mut self, world: &mut World, camera: &CameraView) {
+        let state = self.state.get_or_insert_with(|| SystemState::new(world));
+        let (mut ctx, query, meshes, mut pipelines, mut stats) = state.get_mut(world);
+
+        let transparent = M::blend_state().is_some();
+        let mut draws: Vec<_> = query
+            .iter()
+            .filter_map(
+                |(entity, material, mesh, mesh_handle, transform, color, layer)| {
+                    let mesh =
+                        mesh.or_else(|| mesh_handle.and_then(|handle| meshes.get(handle)))?;
+                    let model = transform.compute_matrix();
+                    let order = DrawOrder::new(layer, transparent, &model);
+                    camera
+                        .sees(order.layer)
+                        .then_some((order, entity, material, mesh, model, color))
+                },
+            )
+            .collect();
+        draws.sort_by(|a, b| a.0.compare(&b.0).then(a.1.cmp(&b.1)));
+
+        let mut current = None;
+        for (_, _, material, mesh, model, color) in draws {
+            let primitive_type = mesh.primitive_type();
+            if current != Some(primitive_type) {
+                match pipelines.get_or_build::<M>(&mut ctx, primitive_type) {
+                    Some(pipeline) => ctx.apply_pipeline(pipeline),
+                    None => break,
+                }
+                current = Some(primitive_type);
+            }
+            ctx.apply_bindings(&mesh.to_bindings(material.textures()));
+            ctx.apply_uniforms(&material.uniform(model, camera.view_projection, color));
+
+            ctx.draw(0, mesh.element_count(), 1);
+            stats.draw_calls += 1;
+            stats.instances += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Component)]
+    struct Flat;
+
+    impl Material for Flat {
+        type Uniform = Mat4;
+
+        fn shader() -> ShaderSource {
+            ShaderSource::parse("#shader vertex\n#shader fragment\n").unwrap()
+        }
+
+        fn meta() -> ShaderMeta {
+            ShaderMeta {
+                uniforms: miniquad::UniformBlockLayout {
+                    uniforms: Vec::new(),
+                },
+                images: Vec::new(),
+            }
+        }
+
+        fn uniform(&self, model: Mat4, projection: Mat4, _: Option<&MeshColor>) -> Mat4 {
+            projection * model
+        }
+    }
+
+    #[test]
+    fn material_entities_are_marked() {
+        let mut world = World::default();
+        let mut stage = SystemStage::single_threaded().with_system(mark_materials::<Flat>);
+        let entity = world.spawn().insert(Flat).id();
+        stage.run(&mut world);
+        assert!(world.get::<WithMaterial>(entity).is_some());
+
+        world.entity_mut(entity).remove::<Flat>();
+        stage.run(&mut world);
+        assert!(world.get::<WithMaterial>(entity).is_none());
+    }
+}
@@ -0,0 +1,147 @@
+use miniquad::PrimitiveType;
+
+use crate::prelude::*;
+
+use super::shaders::Vertex;
+
+/// CPU side geometry of a `SimpleMesh`, centered on the origin in the XY plane
+#[derive(Debug, Clone)]
+pub struct MeshData {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u16>,
+    pub primitive_type: PrimitiveType,
+}
+
+impl MeshData {
+    /// Largest number of `grid` columns or rows, two vertices per line
+    pub const MAX_GRID_CELLS: u16 = u16::MAX / 4;
+
+    pub fn new(vertices: Vec<Vertex>, indices: Vec<u16>, primitive_type: PrimitiveType) -> Self {
+        Self {
+            vertices,
+            indices,
+            primitive_type,
+        }
+    }
+
+    /// A `size` wide quad, its uvs span the whole texture
+    pub fn quad(size: Vec2) -> Self {
+        let half = size / 2.0;
+        #[rustfmt::skip]
+        let vertices = vec![
+            Vertex { position: Vec3::new(-half.x, -half.y, 0.0), uv: Vec2::new(0., 0.) },
+            Vertex { position: Vec3::new( half.x, -half.y, 0.0), uv: Vec2::new(1., 0.) },
+            Vertex { position: Vec3::new( half.x,  half.y, 0.0), uv: Vec2::new(1., 1.) },
+            Vertex { position: Vec3::new(-half.x,  half.y, 0.0), uv: Vec2::new(0., 1.) },
+        ];
+        Self::new(vertices, vec![0, 1, 2, 0, 2, 3], PrimitiveType::Triangles)
+    }
+
+    /// A triangle fan approximating a circle with `segments` sides
+    pub fn circle(radius: f32, segments: u16) -> Self {
+        Self::regular_polygon(radius, segments, 0.0)
+    }
+
+    /// A triangle fan around the center, `rotation` is in degrees.
+    ///
+    /// Uvs map the circumscribed square of the polygon to the whole texture.
+    pub fn regular_polygon(radius: f32, sides: u16, rotation: f32) -> Self {
+        let sides = sides.max(3);
+        let rotation = rotation.to_radians();
+        let step = std::f32::consts::TAU / sides as f32;
+
+        let mut vertices = Vec::with_capacity(sides as usize + 1);
+        vertices.push(Vertex {
+            position: Vec3::ZERO,
+            uv: Vec2::new(0.5, 0.5),
+        });
+        vertices.extend((0..sides).map(|side| {
+            let angle = rotation + step * side as f32;
+            let direction = Vec2::new(angle.cos(), angle.sin());
+            Vertex {
+                position: (direction * radius).extend(0.0),
+                uv: direction * 0.5 + Vec2::splat(0.5),
+            }
+        }));
+        let indices = (0..sides)
+            .flat_map(|i| [0, 1 + i, 1 + (i + 1) % sides])
+            .collect();
+        Self::new(vertices, indices, PrimitiveType::Triangles)
+    }
+
+    /// The lines of a `columns` by `rows` grid of `cell_size` cells, empty when the grid has no
+    /// area.
+    ///
+    /// `columns` and `rows` are clamped to `MeshData::MAX_GRID_CELLS` so every vertex stays
+    /// addressable by a `u16` index.
+    pub fn grid(columns: u16, rows: u16, cell_size: Vec2) -> Self {
+        let columns = columns.min(Self::MAX_GRID_CELLS);
+        let rows = rows.min(Self::MAX_GRID_CELLS);
+        let size = Vec2::new(columns as f32, rows as f32) * cell_size;
+        if size.x == 0.0 || size.y == 0.0 {
+            return Self::new(Vec::new(), Vec::new(), PrimitiveType::Lines);
+        }
+        let origin = -size / 2.0;
+        let vertex = |point: Vec2| Vertex {
+            position: (origin + point).extend(0.0),
+            uv: point / size,
+        };
+
+        let mut vertices = Vec::with_capacity((columns as usize + rows as usize + 2) * 2);
+        for column in 0..=columns {
+            let x = column as f32 * cell_size.x;
+            vertices.push(vertex(Vec2::new(x, 0.0)));
+            vertices.push(vertex(Vec2::new(x, size.y)));
+        }
+        for row in 0..=rows {
+            let y = row as f32 * cell_size.y;
+            vertices.push(vertex(Vec2::new(0.0, y)));
+            vertices.push(vertex(Vec2::new(size.x, y)));
+        }
+        let indices = (0..vertices.len()).map(|index| index as u16).collect();
+        Self::new(vertices, indices, PrimitiveType::Lines)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn polygon_is_a_fan() {
+        let mesh = MeshData::regular_polygon(2.0, 5, 0.0);
+        assert_eq!(mesh.vertices.len(), 6);
+        assert_eq!(mesh.indices.len(), 5 * 3);
+        assert_eq!(&mesh.indices[12..], &[0, 5, 1]);
+        assert_eq!(mesh.vertices[1].position, Vec3::new(2.0, 0.0, 0.0));
+        assert_eq!(mesh.vertices[1].uv, Vec2::new(1.0, 0.5));
+    }
+
+    #[test]
+    fn grid_without_area_is_empty() {
+        assert!(MeshData::grid(0, 4, Vec2::ONE).vertices.is_empty());
+        assert!(MeshData::grid(4, 0, Vec2::ONE).indices.is_empty());
+        assert!(MeshData::grid(4, 4, Vec2::new(1.0, 0.0))
+            .vertices
+            .is_empty());
+    }
+
+    #[test]
+    fn grid_is_made_of_lines() {
+        let mesh = MeshData::grid(3, 2, Vec2::ONE);
+        assert_eq!(mesh.primitive_type, PrimitiveType::Lines);
+        assert_eq!(mesh.indices.len(), (4 + 3) * 2);
+        assert_eq!(mesh.vertices[0].position, Vec3::new(-1.5, -1.0, 0.0));
+        assert_eq!(
+            mesh.vertices.last().unwrap().position,
+            Vec3::new(1.5, 1.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn huge_grids_are_clamped_to_u16_indices() {
+        let mesh = MeshData::grid(u16::MAX, u16::MAX, Vec2::ONE);
+        assert_eq!(mesh.vertices.len(), u16::MAX as usize + 1);
+        assert_eq!(mesh.indices.last(), Some(&u16::MAX));
+    }
+}
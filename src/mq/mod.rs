@@ -1,17 +1,45 @@
+mod animation;
+mod assets;
+mod camera;
+mod hot_reload;
+mod layer;
 mod maps;
+mod material;
+mod mesh;
 pub mod shaders;
 mod shapes;
+mod sprite;
 mod text;
+mod texture;
+mod window;
 
 use bevy_app::{App, CoreStage, Plugin};
-use bevy_ecs::prelude::*;
+use bevy_ecs::{event::Events, prelude::*};
 use bevy_math::*;
+use bevy_utils::tracing::warn;
 use miniquad::*;
 
-use crate::input::{ButtonState, FrameInput, Window};
+use crate::{
+    gamepad::{self, GamepadEvent, GamepadSource, Gamepads},
+    input::{self, ButtonState, FrameInput, ReceivedCharacter, Window},
+};
+use components::{SimpleMesh, SimpleMeshTexture};
 
-pub use shapes::DebugShape2D;
-pub use text::DebugText;
+pub use animation::{
+    AnimatedSprite, AnimationClip, AnimationCompleted, AnimationFrame, PlaybackMode,
+};
+pub use assets::{Assets, Handle, LoadState};
+pub use camera::{Camera, CameraView, Cameras, ClearColor, ClearColorConfig, Ray};
+pub use hot_reload::{HotReload, HotReloadPlugin};
+pub use layer::{RenderLayer, RenderLayers};
+pub use material::{Material, MaterialPipelines, MaterialPlugin};
+pub use mesh::MeshData;
+pub use shaders::ShaderSource;
+pub use shapes::{DebugShape2D, DrawType};
+pub use sprite::{Anchor, Rect, Sprite, SpriteTexture, TextureAtlas};
+pub use text::{DebugText, Font, FontError, Fonts, TextDimensions, TextParams};
+pub use texture::{Image, ImageFormat, TextureError};
+pub use window::WindowDescriptor;
 
 #[derive(Debug, PartialEq, Eq, Hash, Clone, StageLabel)]
 pub struct RenderStage;
@@ -19,31 +47,166 @@ pub struct RenderStage;
 #[derive(Debug, PartialEq, Eq, Hash, Clone, StageLabel)]
 pub struct InputProcessing;
 
+/// Fulfills the requests made to every `Assets` during the frame, right before rendering
+#[derive(Debug, PartialEq, Eq, Hash, Clone, StageLabel)]
+pub struct AssetLoading;
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, SystemLabel)]
+pub enum RenderSystem {
+    Stats,
+    /// Gathers the `Cameras` of the frame
+    Cameras,
+    /// Draws the cameras one after the other, each with every `RenderPass`
+    Draw,
+    Text,
+}
+
+/// Order of the passes drawn for every camera
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) enum RenderPass {
+    Quad,
+    /// Every `MaterialPlugin` pass
+    Materials,
+    Shape,
+}
+
+/// Rendering options read every frame by the `RenderStage`
+#[derive(Debug, Clone, Copy)]
+pub struct RenderSettings {
+    /// Draws every entity sharing a `SimpleMesh` and `SimpleMeshTexture` with a single instanced
+    /// draw call. Turn it off on targets without instancing support to fall back to a draw call
+    /// per entity.
+    pub instancing: bool,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self { instancing: true }
+    }
+}
+
+/// Input options read on every platform event
+#[derive(Debug, Default, Clone, Copy)]
+pub struct InputSettings {
+    /// Also reports the first active touch as the mouse and its left button
+    pub touch_emulates_mouse: bool,
+}
+
+/// Counters of the last rendered frame
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RenderStats {
+    pub draw_calls: usize,
+    pub instances: usize,
+}
+
 pub mod components {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
     use crate::prelude::*;
     use miniquad::*;
 
-    use super::shaders::Vertex;
+    use super::{shaders::Vertex, Image, ImageFormat, MeshData, TextureError};
 
-    #[derive(Debug, Clone, Copy, Component)]
-    pub struct Projection {
-        pub aspect_ratio: f32,
-        pub field_of_view: f32,
+    static NEXT_MESH_ID: AtomicUsize = AtomicUsize::new(0);
+
+    /// Identifies the GPU buffers of a `SimpleMesh`, clones share the same id
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    pub struct MeshId(usize);
+
+    /// How the camera of the same entity maps its view to the viewport, the view is the
+    /// inverse of the camera `Transform`
+    #[derive(Debug, Clone, Copy, PartialEq, Component)]
+    pub enum Projection {
+        /// Parallel projection, everything from `-Z_FAR` to `Z_FAR` along the view axis is
+        /// visible
+        Orthographic(ScalingMode),
+        /// `fov_y` is the vertical field of view in degrees, everything from `Z_NEAR` to `Z_FAR`
+        /// in front of the camera is visible
+        Perspective { fov_y: f32 },
     }
 
     impl Default for Projection {
         fn default() -> Self {
-            Self {
-                aspect_ratio: 1.0,
-                field_of_view: 45.0,
+            Projection::Orthographic(ScalingMode::FixedHeight(45.0))
+        }
+    }
+
+    impl Projection {
+        /// Projection matrix for a viewport of `viewport` pixels
+        pub fn matrix(&self, viewport: Vec2) -> Mat4 {
+            match *self {
+                Projection::Orthographic(scaling_mode) => {
+                    let size = scaling_mode.visible_size(viewport);
+                    let (left, bottom) = match scaling_mode {
+                        // Keeps pixel centers on texel centers when the viewport is odd sized
+                        ScalingMode::PixelPerfect(_) | ScalingMode::WindowSize => {
+                            let pixel = size / viewport.max(Vec2::ONE);
+                            (-(viewport / 2.0).floor() * pixel).into()
+                        }
+                        _ => (-size / 2.0).into(),
+                    };
+                    Mat4::orthographic_rh_gl(
+                        left,
+                        left + size.x,
+                        bottom,
+                        bottom + size.y,
+                        -Z_FAR,
+                        Z_FAR,
+                    )
+                }
+                Projection::Perspective { fov_y } => Mat4::perspective_rh_gl(
+                    fov_y.to_radians(),
+                    aspect_ratio(viewport),
+                    Z_NEAR,
+                    Z_FAR,
+                ),
+            }
+        }
+    }
+
+    /// Size of the world area an orthographic `Projection` shows
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum ScalingMode {
+        /// This many world units fill the viewport height, the width follows the aspect ratio
+        FixedHeight(f32),
+        /// This many world units fill the viewport width, the height follows the aspect ratio
+        FixedWidth(f32),
+        /// A world unit is this many whole pixels
+        PixelPerfect(u32),
+        /// A world unit is a pixel
+        WindowSize,
+    }
+
+    impl ScalingMode {
+        /// World units covered by a viewport of `viewport` pixels
+        pub fn visible_size(&self, viewport: Vec2) -> Vec2 {
+            let aspect_ratio = aspect_ratio(viewport);
+            match *self {
+                ScalingMode::FixedHeight(height) => Vec2::new(height * aspect_ratio, height),
+                ScalingMode::FixedWidth(width) => Vec2::new(width, width / aspect_ratio),
+                ScalingMode::PixelPerfect(pixels_per_unit) => {
+                    viewport / pixels_per_unit.max(1) as f32
+                }
+                ScalingMode::WindowSize => viewport,
             }
         }
     }
 
+    fn aspect_ratio(viewport: Vec2) -> f32 {
+        if viewport.x > 0.0 && viewport.y > 0.0 {
+            viewport.x / viewport.y
+        } else {
+            1.0
+        }
+    }
+
     #[derive(Debug, Clone, Component)]
     pub struct SimpleMesh {
+        id: MeshId,
         vertex_buffer: Buffer,
         index_buffer: Buffer,
+        element_count: i32,
+        primitive_type: PrimitiveType,
     }
 
     #[derive(Debug, Clone, Component)]
@@ -59,6 +222,35 @@ pub mod components {
                 context, bytes, params,
             ))
         }
+
+        pub fn from_image(
+            context: &mut miniquad::Context,
+            image: &Image,
+            filter: FilterMode,
+        ) -> Self {
+            Self::from_data(context, &image.bytes, image.texture_params(filter))
+        }
+
+        /// Decodes a PNG, QOI or TGA file, see `Image::from_file_bytes`
+        pub fn from_file_bytes(
+            context: &mut miniquad::Context,
+            bytes: &[u8],
+            format: Option<ImageFormat>,
+            filter: FilterMode,
+        ) -> Result<Self, TextureError> {
+            let image = Image::from_file_bytes(bytes, format)?;
+            Ok(Self::from_image(context, &image, filter))
+        }
+
+        /// Reads and decodes a PNG, QOI or TGA file, see `Image::load`
+        pub fn load(
+            context: &mut miniquad::Context,
+            path: impl AsRef<std::path::Path>,
+            filter: FilterMode,
+        ) -> Result<Self, TextureError> {
+            let image = Image::load(path)?;
+            Ok(Self::from_image(context, &image, filter))
+        }
     }
 
     #[derive(Debug, Clone, Copy, Component)]
@@ -70,16 +262,144 @@ pub mod components {
         }
     }
 
+    /// How a mesh or sprite is combined with what is already drawn, `Opaque` without the
+    /// component. Every mode but `Opaque` is drawn after the opaque entities of its `RenderLayer`.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Component)]
+    pub enum BlendMode {
+        /// Overwrites the destination, alpha is ignored
+        #[default]
+        Opaque,
+        /// Mixes with the destination by the source alpha
+        Alpha,
+        /// Adds the source, scaled by its alpha, to the destination
+        Additive,
+        /// Multiplies the destination by the source, alpha fades towards the destination
+        Multiply,
+        /// Same as `Alpha` for colors already multiplied by their alpha
+        Premultiplied,
+    }
+
+    impl BlendMode {
+        pub const ALL: [BlendMode; 5] = [
+            BlendMode::Opaque,
+            BlendMode::Alpha,
+            BlendMode::Additive,
+            BlendMode::Multiply,
+            BlendMode::Premultiplied,
+        ];
+
+        pub fn is_transparent(&self) -> bool {
+            *self != BlendMode::Opaque
+        }
+
+        /// Whether the quad shader multiplies the color by its alpha before blending
+        pub fn premultiplies(&self) -> bool {
+            *self == BlendMode::Multiply
+        }
+
+        /// Source and destination factors of the color and alpha blends, `None` when opaque
+        pub fn blend_factors(&self) -> Option<[(BlendFactor, BlendFactor); 2]> {
+            let source_alpha = BlendFactor::Value(BlendValue::SourceAlpha);
+            let one_minus_source_alpha = BlendFactor::OneMinusValue(BlendValue::SourceAlpha);
+            // Modes adding to or scaling the destination leave its alpha as is
+            let keep_alpha = (BlendFactor::Zero, BlendFactor::One);
+            match self {
+                BlendMode::Opaque => None,
+                BlendMode::Alpha => Some([
+                    (source_alpha, one_minus_source_alpha),
+                    (BlendFactor::One, one_minus_source_alpha),
+                ]),
+                BlendMode::Additive => Some([(source_alpha, BlendFactor::One), keep_alpha]),
+                // The color is premultiplied, so the destination is scaled by `1 - a + a * color`
+                BlendMode::Multiply => Some([
+                    (
+                        BlendFactor::Value(BlendValue::DestinationColor),
+                        one_minus_source_alpha,
+                    ),
+                    keep_alpha,
+                ]),
+                BlendMode::Premultiplied => Some([
+                    (BlendFactor::One, one_minus_source_alpha),
+                    (BlendFactor::One, one_minus_source_alpha),
+                ]),
+            }
+        }
+
+        /// Color and alpha blend states of the pipeline
+        pub fn blend_states(&self) -> (Option<BlendState>, Option<BlendState>) {
+            let blend = |(source, destination)| BlendState::new(Equation::Add, source, destination);
+            match self.blend_factors() {
+                Some([color, alpha]) => (Some(blend(color)), Some(blend(alpha))),
+                None => (None, None),
+            }
+        }
+    }
+
     impl SimpleMesh {
+        /// A mesh of indexed triangles
         pub fn new(ctx: &mut miniquad::Context, vertices: &[Vertex], indices: &[u16]) -> Self {
+            Self::with_primitive(ctx, vertices, indices, PrimitiveType::Triangles)
+        }
+
+        pub fn with_primitive(
+            ctx: &mut miniquad::Context,
+            vertices: &[Vertex],
+            indices: &[u16],
+            primitive_type: PrimitiveType,
+        ) -> Self {
             let vertex_buffer = Buffer::immutable(ctx, BufferType::VertexBuffer, vertices);
             let index_buffer = Buffer::immutable(ctx, BufferType::IndexBuffer, indices);
             Self {
+                id: MeshId(NEXT_MESH_ID.fetch_add(1, Ordering::Relaxed)),
                 vertex_buffer,
                 index_buffer,
+                element_count: indices.len() as i32,
+                primitive_type,
             }
         }
 
+        pub fn from_data(ctx: &mut miniquad::Context, data: &MeshData) -> Self {
+            Self::with_primitive(ctx, &data.vertices, &data.indices, data.primitive_type)
+        }
+
+        /// See `MeshData::quad`
+        pub fn quad(ctx: &mut miniquad::Context, size: Vec2) -> Self {
+            Self::from_data(ctx, &MeshData::quad(size))
+        }
+
+        /// See `MeshData::circle`
+        pub fn circle(ctx: &mut miniquad::Context, radius: f32, segments: u16) -> Self {
+            Self::from_data(ctx, &MeshData::circle(radius, segments))
+        }
+
+        /// See `MeshData::regular_polygon`
+        pub fn regular_polygon(
+            ctx: &mut miniquad::Context,
+            radius: f32,
+            sides: u16,
+            rotation: f32,
+        ) -> Self {
+            Self::from_data(ctx, &MeshData::regular_polygon(radius, sides, rotation))
+        }
+
+        /// See `MeshData::grid`
+        pub fn grid(ctx: &mut miniquad::Context, columns: u16, rows: u16, cell_size: Vec2) -> Self {
+            Self::from_data(ctx, &MeshData::grid(columns, rows, cell_size))
+        }
+
+        pub fn id(&self) -> MeshId {
+            self.id
+        }
+
+        /// Number of indices drawn
+        pub fn element_count(&self) -> i32 {
+            self.element_count
+        }
+
+        pub fn primitive_type(&self) -> PrimitiveType {
+            self.primitive_type
+        }
+
         pub fn to_bindings(&self, images: impl Into<Option<Vec<Texture>>>) -> miniquad::Bindings {
             let images = images.into().unwrap_or_default();
             miniquad::Bindings {
@@ -89,91 +409,890 @@ pub mod components {
             }
         }
     }
+
+    /// A mesh whose geometry can change every frame, uploaded to stream buffers during the
+    /// `RenderStage`. The buffers grow when the geometry outgrows them and are never shrunk.
+    #[derive(Debug, Component)]
+    pub struct DynamicMesh {
+        data: MeshData,
+        vertex_buffer: Buffer,
+        index_buffer: Buffer,
+        dirty: bool,
+    }
+
+    impl DynamicMesh {
+        const DEFAULT_VERTICES: usize = 64;
+        const DEFAULT_INDICES: usize = 96;
+
+        pub fn new(ctx: &mut miniquad::Context, primitive_type: PrimitiveType) -> Self {
+            Self::with_capacity(
+                ctx,
+                Self::DEFAULT_VERTICES,
+                Self::DEFAULT_INDICES,
+                primitive_type,
+            )
+        }
+
+        pub fn with_capacity(
+            ctx: &mut miniquad::Context,
+            vertices: usize,
+            indices: usize,
+            primitive_type: PrimitiveType,
+        ) -> Self {
+            Self {
+                data: MeshData::new(
+                    Vec::with_capacity(vertices),
+                    Vec::with_capacity(indices),
+                    primitive_type,
+                ),
+                vertex_buffer: vertex_stream(ctx, vertices.max(1)),
+                index_buffer: index_stream(ctx, indices.max(1)),
+                dirty: false,
+            }
+        }
+
+        pub fn from_data(ctx: &mut miniquad::Context, data: MeshData) -> Self {
+            let mut mesh = Self::with_capacity(
+                ctx,
+                data.vertices.len(),
+                data.indices.len(),
+                data.primitive_type,
+            );
+            mesh.data = data;
+            mesh.dirty = true;
+            mesh
+        }
+
+        /// Replaces the whole geometry
+        pub fn set(&mut self, vertices: &[Vertex], indices: &[u16]) {
+            self.update(|data| {
+                data.vertices.clear();
+                data.vertices.extend_from_slice(vertices);
+                data.indices.clear();
+                data.indices.extend_from_slice(indices);
+            });
+        }
+
+        /// Edits the geometry in place, it is uploaded again before the next draw
+        pub fn update(&mut self, edit: impl FnOnce(&mut MeshData)) {
+            edit(&mut self.data);
+            self.dirty = true;
+        }
+
+        pub fn clear(&mut self) {
+            self.update(|data| {
+                data.vertices.clear();
+                data.indices.clear();
+            });
+        }
+
+        pub fn data(&self) -> &MeshData {
+            &self.data
+        }
+
+        /// Number of indices drawn
+        pub fn element_count(&self) -> i32 {
+            self.data.indices.len() as i32
+        }
+
+        pub fn primitive_type(&self) -> PrimitiveType {
+            self.data.primitive_type
+        }
+
+        /// Number of vertices and indices the GPU buffers can hold without growing
+        pub fn capacity(&self) -> (usize, usize) {
+            (
+                self.vertex_buffer.size() / std::mem::size_of::<Vertex>(),
+                self.index_buffer.size() / std::mem::size_of::<u16>(),
+            )
+        }
+
+        /// Uploads the geometry if it changed, growing the buffers when needed
+        pub(crate) fn upload(&mut self, ctx: &mut miniquad::Context) {
+            if !self.dirty {
+                return;
+            }
+            let (vertices, indices) = self.capacity();
+            if self.data.vertices.len() > vertices {
+                self.vertex_buffer.delete();
+                self.vertex_buffer =
+                    vertex_stream(ctx, grown_capacity(vertices, self.data.vertices.len()));
+            }
+            if self.data.indices.len() > indices {
+                self.index_buffer.delete();
+                self.index_buffer =
+                    index_stream(ctx, grown_capacity(indices, self.data.indices.len()));
+            }
+            self.vertex_buffer.update(ctx, &self.data.vertices);
+            self.index_buffer.update(ctx, &self.data.indices);
+            self.dirty = false;
+        }
+
+        pub fn to_bindings(&self, images: impl Into<Option<Vec<Texture>>>) -> miniquad::Bindings {
+            let images = images.into().unwrap_or_default();
+            miniquad::Bindings {
+                vertex_buffers: vec![self.vertex_buffer],
+                index_buffer: self.index_buffer,
+                images,
+            }
+        }
+    }
+
+    fn vertex_stream(ctx: &mut miniquad::Context, vertices: usize) -> Buffer {
+        Buffer::stream(
+            ctx,
+            BufferType::VertexBuffer,
+            vertices * std::mem::size_of::<Vertex>(),
+        )
+    }
+
+    fn index_stream(ctx: &mut miniquad::Context, indices: usize) -> Buffer {
+        Buffer::stream(
+            ctx,
+            BufferType::IndexBuffer,
+            indices * std::mem::size_of::<u16>(),
+        )
+    }
+
+    /// Doubles `capacity` until `required` fits
+    fn grown_capacity(capacity: usize, required: usize) -> usize {
+        let mut capacity = capacity.max(1);
+        while capacity < required {
+            capacity *= 2;
+        }
+        capacity
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn capacity_doubles_until_it_fits() {
+            assert_eq!(grown_capacity(64, 65), 128);
+            assert_eq!(grown_capacity(64, 300), 512);
+            assert_eq!(grown_capacity(0, 3), 4);
+        }
+
+        #[test]
+        fn orthographic_scaling_modes() {
+            let viewport = Vec2::new(800.0, 400.0);
+            let top_right = |projection: Projection| {
+                projection
+                    .matrix(viewport)
+                    .inverse()
+                    .transform_point3(Vec3::new(1.0, 1.0, 0.0))
+                    .truncate()
+            };
+            let fixed_height = Projection::Orthographic(ScalingMode::FixedHeight(10.0));
+            assert!(top_right(fixed_height).abs_diff_eq(Vec2::new(10.0, 5.0), 1e-4));
+            let fixed_width = Projection::Orthographic(ScalingMode::FixedWidth(10.0));
+            assert!(top_right(fixed_width).abs_diff_eq(Vec2::new(5.0, 2.5), 1e-4));
+            let pixel_perfect = Projection::Orthographic(ScalingMode::PixelPerfect(4));
+            assert!(top_right(pixel_perfect).abs_diff_eq(Vec2::new(100.0, 50.0), 1e-4));
+        }
+
+        #[test]
+        fn odd_window_sizes_keep_whole_pixels() {
+            let projection = Projection::Orthographic(ScalingMode::WindowSize);
+            let left = projection
+                .matrix(Vec2::new(5.0, 4.0))
+                .inverse()
+                .transform_point3(Vec3::new(-1.0, -1.0, 0.0));
+            assert!(left.abs_diff_eq(Vec3::new(-2.0, -2.0, 0.0), 1e-4));
+        }
+
+        #[test]
+        fn perspective_uses_the_prelude_planes() {
+            let projection = Projection::Perspective { fov_y: 90.0 };
+            let matrix = projection.matrix(Vec2::new(100.0, 100.0));
+            let near = matrix.project_point3(Vec3::new(0.0, 0.0, -Z_NEAR));
+            assert!((near.z + 1.0).abs() < 1e-4);
+            let edge = matrix.project_point3(Vec3::new(0.0, 2.0, -2.0));
+            assert!((edge.y - 1.0).abs() < 1e-4);
+        }
+
+        #[test]
+        fn blend_modes_are_listed_by_index() {
+            for (index, blend_mode) in BlendMode::ALL.iter().enumerate() {
+                assert_eq!(*blend_mode as usize, index);
+            }
+            assert_eq!(BlendMode::Opaque.blend_states(), (None, None));
+            assert!(BlendMode::ALL[1..].iter().all(BlendMode::is_transparent));
+        }
+
+        /// `source * source_factor + destination * destination_factor` for every channel
+        fn blend(factors: (BlendFactor, BlendFactor), source: Vec4, destination: Vec4) -> Vec4 {
+            let factor = |factor| {
+                let value = |value| match value {
+                    BlendValue::SourceColor => source,
+                    BlendValue::SourceAlpha => Vec4::splat(source.w),
+                    BlendValue::DestinationColor => destination,
+                    BlendValue::DestinationAlpha => Vec4::splat(destination.w),
+                };
+                match factor {
+                    BlendFactor::Zero => Vec4::ZERO,
+                    BlendFactor::One => Vec4::ONE,
+                    BlendFactor::Value(v) => value(v),
+                    BlendFactor::OneMinusValue(v) => Vec4::ONE - value(v),
+                    BlendFactor::SourceAlphaSaturate => unimplemented!(),
+                }
+            };
+            source * factor(factors.0) + destination * factor(factors.1)
+        }
+
+        #[test]
+        fn multiply_at_half_alpha_fades_towards_the_destination() {
+            let mode = BlendMode::Multiply;
+            let color = Vec4::new(0.2, 0.4, 0.6, 0.5);
+            let destination = Vec4::new(1.0, 0.5, 0.5, 1.0);
+            assert!(mode.premultiplies());
+            let source = (color.truncate() * color.w).extend(color.w);
+
+            let [color_factors, alpha_factors] = mode.blend_factors().unwrap();
+            let blended = blend(color_factors, source, destination);
+            // Halfway between the destination and the destination times the color
+            assert!(blended
+                .truncate()
+                .abs_diff_eq(Vec3::new(0.6, 0.35, 0.4), 1e-6));
+            assert_eq!(blend(alpha_factors, source, destination).w, 1.0);
+        }
+    }
 }
 
 mod systems {
+
+    use bevy_ecs::system::SystemState;
+    use bevy_utils::tracing::warn;
+
     use crate::prelude::*;
 
     use super::{
-        components::{MeshColor, Projection, SimpleMesh, SimpleMeshTexture},
+        camera::{CameraView, Cameras, ClearColor},
+        components::{BlendMode, DynamicMesh, MeshColor, MeshId, SimpleMesh, SimpleMeshTexture},
+        layer::{DrawOrder, RenderLayer},
+        material::WithMaterial,
         shaders::{
-            quad::{QuadPipeline, Uniform},
-            Vertex,
+            quad::{
+                InstancedUniform, QuadInstance, QuadPipeline, Uniform, FULL_UV_RECT, MAX_INSTANCES,
+            },
+            shape::{self, ShapePipeline},
+            text::{self, TextPipeline},
         },
+        sprite::{Sprite, SpriteQuad},
+        Assets, DebugText, Fonts, Handle, RenderPass, RenderSettings, RenderStats,
     };
 
-    fn get_projection(camera: Query<(&Projection, &Transform)>) -> Mat4 {
-        let (projection, transform) = camera
-            .get_single()
-            .ok()
-            .map(|(p, t)| (*p, *t))
-            .unwrap_or((Projection::default(), Transform::identity()));
+    /// Buffers a `QuadDraw` is drawn from
+    #[derive(Clone, Copy)]
+    enum DrawMesh<'a> {
+        Simple(&'a SimpleMesh),
+        Dynamic(Entity, &'a DynamicMesh),
+    }
+
+    /// Draws with the same key share their buffers
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    enum MeshKey {
+        Simple(MeshId),
+        Dynamic(Entity),
+    }
+
+    impl DrawMesh<'_> {
+        fn key(&self) -> MeshKey {
+            match self {
+                DrawMesh::Simple(mesh) => MeshKey::Simple(mesh.id()),
+                DrawMesh::Dynamic(entity, _) => MeshKey::Dynamic(*entity),
+            }
+        }
+
+        fn element_count(&self) -> i32 {
+            match self {
+                DrawMesh::Simple(mesh) => mesh.element_count(),
+                DrawMesh::Dynamic(_, mesh) => mesh.element_count(),
+            }
+        }
+
+        fn primitive_type(&self) -> miniquad::PrimitiveType {
+            match self {
+                DrawMesh::Simple(mesh) => mesh.primitive_type(),
+                DrawMesh::Dynamic(_, mesh) => mesh.primitive_type(),
+            }
+        }
 
-        let Projection {
-            aspect_ratio,
-            field_of_view,
-        } = projection;
-        let top = field_of_view / 2.0;
-        let right = top * aspect_ratio;
-        Mat4::orthographic_rh_gl(-right, right, -top, top, -1., Z_FAR) * transform.compute_matrix()
+        fn bindings(&self, texture: miniquad::Texture) -> miniquad::Bindings {
+            match self {
+                DrawMesh::Simple(mesh) => mesh.to_bindings(vec![texture]),
+                DrawMesh::Dynamic(_, mesh) => mesh.to_bindings(vec![texture]),
+            }
+        }
     }
 
-    pub fn quad_render_pass(
+    /// A `SimpleMesh` entity, a `DynamicMesh` or a `Sprite`, drawn with the quad pipeline
+    struct QuadDraw<'a> {
+        entity: Entity,
+        order: DrawOrder,
+        mesh: DrawMesh<'a>,
+        texture: miniquad::Texture,
+        blend_mode: BlendMode,
+        instance: QuadInstance,
+    }
+
+    impl<'a> QuadDraw<'a> {
+        #[allow(clippy::too_many_arguments)]
+        fn new(
+            entity: Entity,
+            mesh: DrawMesh<'a>,
+            texture: miniquad::Texture,
+            model: Mat4,
+            color: Option<&MeshColor>,
+            uv_rect: Vec4,
+            blend_mode: Option<&BlendMode>,
+            layer: Option<&RenderLayer>,
+        ) -> Self {
+            let blend_mode = blend_mode.copied().unwrap_or_default();
+            Self {
+                entity,
+                order: DrawOrder::new(layer, blend_mode.is_transparent(), &model),
+                mesh,
+                texture,
+                blend_mode,
+                instance: QuadInstance {
+                    model,
+                    color: color.map(|color| color.0).unwrap_or(Color::WHITE).into(),
+                    uv_rect,
+                },
+            }
+        }
+
+        /// Draws that can share an instanced draw call have the same key
+        fn batch_key(&self) -> (MeshKey, u32, BlendMode) {
+            (
+                self.mesh.key(),
+                self.texture.gl_internal_id(),
+                self.blend_mode,
+            )
+        }
+
+        /// `DrawOrder` first, ties are broken by batch so they can be instanced together, then
+        /// by entity so the order does not change between frames
+        fn compare(&self, other: &Self) -> std::cmp::Ordering {
+            self.order
+                .compare(&other.order)
+                .then_with(|| self.batch_key().cmp(&other.batch_key()))
+                .then(self.entity.cmp(&other.entity))
+        }
+    }
+
+    /// Draws sharing a mesh, a texture and a blend mode, drawn together by the instanced path
+    struct InstanceGroup<'a> {
+        mesh: DrawMesh<'a>,
+        texture: miniquad::Texture,
+        blend_mode: BlendMode,
+        instances: Vec<QuadInstance>,
+    }
+
+    /// A texture set directly or through a handle
+    type TextureComponents<'a> = (
+        Option<&'a SimpleMeshTexture>,
+        Option<&'a Handle<SimpleMeshTexture>>,
+    );
+
+    /// A mesh set directly or through a handle, along with what the quad pipeline reads
+    type MeshComponents<'a> = (
+        Entity,
+        Option<&'a SimpleMesh>,
+        Option<&'a Handle<SimpleMesh>>,
+        &'a Transform,
+        DrawComponents<'a>,
+        TextureComponents<'a>,
+    );
+
+    type DynamicMeshComponents<'a> = (
+        Entity,
+        &'a DynamicMesh,
+        &'a Transform,
+        DrawComponents<'a>,
+        TextureComponents<'a>,
+    );
+
+    type SpriteComponents<'a> = (Entity, &'a Sprite, &'a Transform, DrawComponents<'a>);
+
+    /// Optional components every quad draw reads
+    type DrawComponents<'a> = (
+        Option<&'a MeshColor>,
+        Option<&'a BlendMode>,
+        Option<&'a RenderLayer>,
+    );
+
+    /// Entities with a `Material` are drawn by its `MaterialPlugin` instead
+    type WithMesh = (
+        Or<(With<SimpleMesh>, With<Handle<SimpleMesh>>)>,
+        Without<WithMaterial>,
+    );
+
+    /// Texture bound for an entity, `None` while its texture handle is loading
+    fn entity_texture(
+        (texture, handle): TextureComponents,
+        textures: &Assets<SimpleMeshTexture>,
+        white_texture: miniquad::Texture,
+    ) -> Option<miniquad::Texture> {
+        match (texture, handle) {
+            (Some(texture), _) => Some(texture.0),
+            (None, Some(handle)) => textures.get(handle).map(|texture| texture.0),
+            (None, None) => Some(white_texture),
+        }
+    }
+
+    pub fn reset_render_stats(mut stats: ResMut<RenderStats>) {
+        *stats = RenderStats::default();
+    }
+
+    /// Draws into the render pass of a camera, see `CameraPasses`
+    pub(crate) trait CameraPass: Send + Sync + 'static {
+        fn draw(&mut self, world: &mut World, camera: &CameraView);
+
+        /// Called once every camera is drawn
+        fn finish(&mut self, _world: &mut World) {}
+    }
+
+    /// Passes drawn for every camera, in `RenderPass` order
+    #[derive(Default)]
+    pub(crate) struct CameraPasses {
+        passes: Vec<(RenderPass, Box<dyn CameraPass>)>,
+    }
+
+    impl CameraPasses {
+        /// Draws `pass` after the passes added before it with the same `order`
+        pub fn add(&mut self, order: RenderPass, pass: impl CameraPass) {
+            let index = self.passes.partition_point(|(other, _)| *other <= order);
+            self.passes.insert(index, (order, Box::new(pass)));
+        }
+
+        /// Draws every pass for a camera before moving to the next one, `begin` and `end` wrap
+        /// the passes of each camera
+        fn draw(
+            &mut self,
+            world: &mut World,
+            cameras: &[CameraView],
+            mut begin: impl FnMut(&mut World, &CameraView),
+            mut end: impl FnMut(&mut World),
+        ) {
+            for camera in cameras {
+                begin(world, camera);
+                for (_, pass) in self.passes.iter_mut() {
+                    pass.draw(world, camera);
+                }
+                end(world);
+            }
+            for (_, pass) in self.passes.iter_mut() {
+                pass.finish(world);
+            }
+        }
+    }
+
+    fn context(world: &mut World) -> Mut<'_, miniquad::Context> {
+        world
+            .get_resource_mut::<miniquad::Context>()
+            .expect("Context MUST be in the App Resources")
+    }
+
+    /// Draws the cameras by priority, a camera is drawn with every pass before the next one
+    /// starts so higher priority cameras cover the lower ones
+    pub fn render_cameras(world: &mut World) {
+        world.resource_scope(|world, mut passes: Mut<CameraPasses>| {
+            let cameras: Vec<CameraView> = world
+                .get_resource::<Cameras>()
+                .map(|cameras| cameras.iter().copied().collect())
+                .unwrap_or_default();
+            if cameras.is_empty() {
+                let clear_color = world
+                    .get_resource::<ClearColor>()
+                    .copied()
+                    .unwrap_or_default();
+                let (r, g, b, a) = ClearColor::framebuffer_rgba(clear_color.0);
+                let mut ctx = context(world);
+                ctx.begin_default_pass(miniquad::PassAction::clear_color(r, g, b, a));
+                ctx.end_render_pass();
+            }
+
+            passes.draw(
+                world,
+                &cameras,
+                |world, camera| {
+                    let mut ctx = context(world);
+                    camera.begin_pass(&mut ctx);
+                    camera.clear(&mut ctx);
+                },
+                |world| context(world).end_render_pass(),
+            );
+        });
+    }
+
+    pub fn upload_dynamic_meshes(
         mut ctx: ResMut<miniquad::Context>,
-        mesh: Query<(
-            &SimpleMesh,
-            &Transform,
-            Option<&MeshColor>,
-            Option<&SimpleMeshTexture>,
-        )>,
-        camera: Query<(&Projection, &Transform)>,
-        pipeline: Res<QuadPipeline>,
+        mut query: Query<&mut DynamicMesh>,
     ) {
-        let projection = get_projection(camera);
+        for mut mesh in query.iter_mut() {
+            mesh.upload(&mut ctx);
+        }
+    }
 
-        ctx.begin_default_pass(Default::default());
-        ctx.clear(Some((0.13, 0.137, 0.137, 1.0)), None, None);
+    type QuadParams = (
+        ResMut<'static, miniquad::Context>,
+        Query<'static, 'static, MeshComponents<'static>, WithMesh>,
+        Query<'static, 'static, SpriteComponents<'static>>,
+        Query<'static, 'static, DynamicMeshComponents<'static>>,
+        Res<'static, Assets<SimpleMesh>>,
+        Res<'static, Assets<SimpleMeshTexture>>,
+        Res<'static, QuadPipeline>,
+        Res<'static, SpriteQuad>,
+        Res<'static, RenderSettings>,
+        ResMut<'static, RenderStats>,
+    );
 
-        ctx.apply_pipeline(pipeline.as_ref());
+    /// Draws the `SimpleMesh` entities, the `DynamicMesh` entities and the `Sprite`s
+    #[derive(Default)]
+    pub(crate) struct QuadPass {
+        /// Created on the first draw, once the pipelines are inserted
+        state: Option<SystemState<QuadParams>>,
+    }
 
-        for (mesh, transform, color, texture) in mesh.iter() {
-            let texture = texture.map(|m| vec![m.0]);
-            let bindings = mesh.to_bindings(texture);
-            let color: Vec4 = color.map(|color| color.0).unwrap_or(Color::WHITE).into();
-            ctx.apply_bindings(&bindings);
+    impl CameraPass for QuadPass {
+        fn draw(&mut self, world: &mut World, camera: &CameraView) {
+            let state = self.state.get_or_insert_with(|| SystemState::new(world));
+            let (
+                mut ctx,
+                mesh,
+                sprite,
+                dynamic_mesh,
+                meshes,
+                textures,
+                pipeline,
+                sprite_quad,
+                settings,
+                mut stats,
+            ) = state.get_mut(world);
+
+            // Entities whose mesh or texture handle is still loading are skipped
+            let meshes = mesh.iter().filter_map(
+                |(entity, mesh, mesh_handle, transform, (color, blend_mode, layer), texture)| {
+                    let mesh =
+                        mesh.or_else(|| mesh_handle.and_then(|handle| meshes.get(handle)))?;
+                    Some(QuadDraw::new(
+                        entity,
+                        DrawMesh::Simple(mesh),
+                        entity_texture(texture, &textures, pipeline.white_texture)?,
+                        transform.compute_matrix(),
+                        color,
+                        FULL_UV_RECT.into(),
+                        blend_mode,
+                        layer,
+                    ))
+                },
+            );
+            let sprites = sprite.iter().filter_map(
+                |(entity, sprite, transform, (color, blend_mode, layer))| {
+                    let texture = sprite.texture.resolve(&textures)?;
+                    Some(QuadDraw::new(
+                        entity,
+                        DrawMesh::Simple(&sprite_quad.0),
+                        texture,
+                        sprite.model(transform, texture),
+                        color,
+                        sprite.uv_rect(texture),
+                        blend_mode,
+                        layer,
+                    ))
+                },
+            );
+            let dynamic_meshes = dynamic_mesh.iter().filter_map(
+                |(entity, mesh, transform, (color, blend_mode, layer), texture)| {
+                    if mesh.element_count() == 0 {
+                        return None;
+                    }
+                    Some(QuadDraw::new(
+                        entity,
+                        DrawMesh::Dynamic(entity, mesh),
+                        entity_texture(texture, &textures, pipeline.white_texture)?,
+                        transform.compute_matrix(),
+                        color,
+                        FULL_UV_RECT.into(),
+                        blend_mode,
+                        layer,
+                    ))
+                },
+            );
+
+            let mut draws: Vec<QuadDraw> = meshes
+                .chain(sprites)
+                .chain(dynamic_meshes)
+                .filter(|draw| camera.sees(draw.order.layer))
+                .collect();
+            draws.sort_by(QuadDraw::compare);
+
+            let projection = camera.view_projection;
+            let draws: Vec<&QuadDraw> = draws.iter().collect();
+            if settings.instancing {
+                let groups = group_instances(&draws);
+                draw_instanced(&mut ctx, &groups, projection, &pipeline, &mut stats);
+            } else {
+                draw_each(&mut ctx, &draws, projection, &pipeline, &mut stats);
+            }
+        }
+    }
+
+    fn draw_each(
+        ctx: &mut miniquad::Context,
+        draws: &[&QuadDraw],
+        projection: Mat4,
+        pipeline: &QuadPipeline,
+        stats: &mut RenderStats,
+    ) {
+        let mut current = None;
+        for draw in draws {
+            let variant = (draw.mesh.primitive_type(), draw.blend_mode);
+            if current != Some(variant) {
+                current = Some(variant);
+                ctx.apply_pipeline(&pipeline.variant(variant.0, variant.1).single);
+            }
+            ctx.apply_bindings(&draw.mesh.bindings(draw.texture));
             ctx.apply_uniforms(&Uniform {
-                color,
+                color: draw.instance.color,
                 projection,
-                model: transform.compute_matrix(),
+                model: draw.instance.model,
+                uv_rect: draw.instance.uv_rect,
+                premultiply: draw.blend_mode.premultiplies().into(),
             });
 
-            ctx.draw(0, 6, 1);
+            ctx.draw(0, draw.mesh.element_count(), 1);
+            stats.draw_calls += 1;
+            stats.instances += 1;
         }
+    }
 
-        ctx.end_render_pass();
+    /// Groups consecutive draws sharing a mesh, a texture and a blend mode, so the groups are
+    /// drawn in the order of `draws`
+    fn group_instances<'a>(draws: &[&QuadDraw<'a>]) -> Vec<InstanceGroup<'a>> {
+        let mut groups: Vec<InstanceGroup> = Vec::new();
+        let mut last_key = None;
+        for draw in draws {
+            let key = draw.batch_key();
+            if last_key != Some(key) {
+                last_key = Some(key);
+                groups.push(InstanceGroup {
+                    mesh: draw.mesh,
+                    texture: draw.texture,
+                    blend_mode: draw.blend_mode,
+                    instances: Vec::new(),
+                });
+            }
+            if let Some(group) = groups.last_mut() {
+                group.instances.push(draw.instance);
+            }
+        }
+        groups
     }
 
-    pub fn gather_aspect_ratio(frame_input: Res<FrameInput>, mut query: Query<&mut Projection>) {
+    fn draw_instanced(
+        ctx: &mut miniquad::Context,
+        groups: &[InstanceGroup],
+        projection: Mat4,
+        pipeline: &QuadPipeline,
+        stats: &mut RenderStats,
+    ) {
+        let mut current = None;
+        for group in groups {
+            let variant = (group.mesh.primitive_type(), group.blend_mode);
+            if current != Some(variant) {
+                current = Some(variant);
+                ctx.apply_pipeline(&pipeline.variant(variant.0, variant.1).instanced);
+                ctx.apply_uniforms(&InstancedUniform {
+                    projection,
+                    premultiply: group.blend_mode.premultiplies().into(),
+                });
+            }
+            let mut bindings = group.mesh.bindings(group.texture);
+            bindings.vertex_buffers.push(pipeline.instance_buffer);
+
+            for instances in group.instances.chunks(MAX_INSTANCES) {
+                pipeline.instance_buffer.update(ctx, instances);
+                ctx.apply_bindings(&bindings);
+
+                ctx.draw(0, group.mesh.element_count(), instances.len() as i32);
+                stats.draw_calls += 1;
+                stats.instances += instances.len();
+            }
+        }
+    }
+
+    type ShapeParams = (
+        ResMut<'static, miniquad::Context>,
+        Res<'static, DebugShape2D>,
+        Res<'static, ShapePipeline>,
+        ResMut<'static, RenderStats>,
+    );
+
+    /// Draws the `DebugShape2D` of the frame
+    #[derive(Default)]
+    pub(crate) struct ShapePass {
+        /// Created on the first draw, once the pipeline is inserted
+        state: Option<SystemState<ShapeParams>>,
+    }
+
+    impl CameraPass for ShapePass {
+        fn draw(&mut self, world: &mut World, camera: &CameraView) {
+            let state = self.state.get_or_insert_with(|| SystemState::new(world));
+            let (mut ctx, shapes, pipeline, mut stats) = state.get_mut(world);
+            // Shapes are on the default layer
+            if shapes.is_empty() || !camera.sees(RenderLayer::default()) {
+                return;
+            }
+
+            ctx.apply_pipeline(pipeline.as_ref());
+            for batch in shapes.batches() {
+                pipeline.bindings.vertex_buffers[0].update(&mut ctx, &batch.vertices);
+                pipeline
+                    .bindings
+                    .index_buffer
+                    .update(&mut ctx, &batch.indices);
+                ctx.apply_bindings(&pipeline.bindings);
+                ctx.apply_uniforms(&shape::Uniform {
+                    projection: camera.view_projection,
+                });
+
+                ctx.draw(0, batch.indices.len() as i32, 1);
+                stats.draw_calls += 1;
+            }
+        }
+
+        fn finish(&mut self, world: &mut World) {
+            if let Some(mut shapes) = world.get_resource_mut::<DebugShape2D>() {
+                shapes.clear();
+            }
+        }
+    }
+
+    pub fn text_render_pass(
+        mut ctx: ResMut<miniquad::Context>,
+        mut text: ResMut<DebugText>,
+        mut fonts: ResMut<Fonts>,
+        frame_input: Res<FrameInput>,
+        pipeline: Res<TextPipeline>,
+        mut stats: ResMut<RenderStats>,
+    ) {
+        if text.is_empty() {
+            return;
+        }
         let window = frame_input.window;
-        query.iter_mut().for_each(|mut projection| {
-            projection.aspect_ratio = window.width / window.height;
-        });
+        let projection = Mat4::orthographic_rh_gl(0.0, window.width, window.height, 0.0, -1.0, 1.0);
+        let missing = text.cache_glyphs(&mut fonts);
+        if missing > 0 {
+            warn!(
+                "{} glyphs did not fit the glyph atlas along with the rest of the frame",
+                missing
+            );
+        }
+        let mut bindings = pipeline.bindings.clone();
+        bindings.images = vec![fonts.atlas_mut().texture(&mut ctx)];
+
+        ctx.begin_default_pass(miniquad::PassAction::Nothing);
+        ctx.apply_pipeline(pipeline.as_ref());
+
+        for (vertices, indices) in text.batches(&fonts) {
+            bindings.vertex_buffers[0].update(&mut ctx, &vertices);
+            bindings.index_buffer.update(&mut ctx, &indices);
+            ctx.apply_bindings(&bindings);
+            ctx.apply_uniforms(&text::Uniform { projection });
+
+            ctx.draw(0, indices.len() as i32, 1);
+            stats.draw_calls += 1;
+        }
+
+        ctx.end_render_pass();
+        text.clear();
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use std::sync::{Arc, Mutex};
+
+        use super::*;
+        use crate::mq::{camera::gather_cameras, components::Projection, Camera};
+
+        type Log = Arc<Mutex<Vec<(Entity, RenderPass)>>>;
+
+        struct Record(RenderPass, Log);
+
+        impl CameraPass for Record {
+            fn draw(&mut self, _: &mut World, camera: &CameraView) {
+                self.1.lock().unwrap().push((camera.entity, self.0));
+            }
+        }
+
+        #[test]
+        fn cameras_draw_every_pass_before_the_next_one() {
+            let mut world = World::default();
+            world.insert_resource(FrameInput::default());
+            world.insert_resource(Cameras::default());
+            world.insert_resource(ClearColor::default());
+            let hud = world
+                .spawn()
+                .insert_bundle((Projection::default(), Transform::identity()))
+                .insert(Camera::overlay(1))
+                .id();
+            let main = world
+                .spawn()
+                .insert_bundle((Projection::default(), Transform::identity()))
+                .id();
+            SystemStage::single_threaded()
+                .with_system(gather_cameras)
+                .run(&mut world);
+
+            let log = Log::default();
+            let mut passes = CameraPasses::default();
+            passes.add(RenderPass::Shape, Record(RenderPass::Shape, log.clone()));
+            passes.add(RenderPass::Quad, Record(RenderPass::Quad, log.clone()));
+            passes.add(
+                RenderPass::Materials,
+                Record(RenderPass::Materials, log.clone()),
+            );
+            let cameras: Vec<CameraView> = world
+                .get_resource::<Cameras>()
+                .unwrap()
+                .iter()
+                .copied()
+                .collect();
+            passes.draw(&mut world, &cameras, |_, _| {}, |_| {});
+
+            assert_eq!(
+                *log.lock().unwrap(),
+                vec![
+                    (main, RenderPass::Quad),
+                    (main, RenderPass::Materials),
+                    (main, RenderPass::Shape),
+                    (hud, RenderPass::Quad),
+                    (hud, RenderPass::Materials),
+                    (hud, RenderPass::Shape),
+                ]
+            );
+        }
     }
 }
 
 pub fn miniquad_runner(mut app: App) {
-    let window_width = 1024;
-    let window_height = 768;
-    let config = conf::Conf {
-        window_title: "FlappyBird".to_string(),
-        window_width,
-        window_height,
-        ..Default::default()
-    };
+    let descriptor = app
+        .world
+        .get_resource::<WindowDescriptor>()
+        .cloned()
+        .unwrap_or_default();
+    if let Err(error) = descriptor.icon() {
+        warn!("the window icon is left out: {}", error);
+    }
+    let config = descriptor.conf();
     let first_frame_input = FrameInput {
         window: Window {
-            width: window_width as f32,
-            height: window_height as f32,
+            width: descriptor.width as f32,
+            height: descriptor.height as f32,
         },
         ..Default::default()
     };
@@ -191,7 +1310,20 @@ impl Plugin for MiniquadPlugin {
         app.set_runner(miniquad_runner)
             .init_resource::<DebugShape2D>()
             .init_resource::<DebugText>()
+            .init_resource::<Fonts>()
             .init_resource::<FrameInput>()
+            .init_resource::<RenderSettings>()
+            .init_resource::<InputSettings>()
+            .init_resource::<Gamepads>()
+            .init_resource::<GamepadSource>()
+            .init_resource::<RenderStats>()
+            .init_resource::<Cameras>()
+            .init_resource::<ClearColor>()
+            .init_resource::<WindowDescriptor>()
+            .init_resource::<Assets<SimpleMeshTexture>>()
+            .init_resource::<Assets<SimpleMesh>>()
+            .init_resource::<Assets<Font>>()
+            .init_resource::<Assets<ShaderSource>>()
             .add_stage_before(
                 CoreStage::PreUpdate,
                 InputProcessing,
@@ -202,8 +1334,50 @@ impl Plugin for MiniquadPlugin {
                 RenderStage,
                 SystemStage::single_threaded(),
             )
-            .add_system_to_stage(InputProcessing, systems::gather_aspect_ratio)
-            .add_system_to_stage(RenderStage, systems::quad_render_pass);
+            .add_stage_before(RenderStage, AssetLoading, SystemStage::parallel())
+            .add_event::<AnimationCompleted>()
+            .add_event::<ReceivedCharacter>()
+            .add_event::<GamepadEvent>()
+            .add_system_to_stage(InputProcessing, gamepad::poll_gamepads)
+            .add_system_to_stage(InputProcessing, camera::gather_cameras)
+            .add_system_to_stage(CoreStage::PostUpdate, animation::animate_sprites)
+            .add_system_to_stage(AssetLoading, assets::load_textures)
+            .add_system_to_stage(AssetLoading, assets::load_meshes)
+            .add_system_to_stage(AssetLoading, assets::load_fonts)
+            .add_system_to_stage(AssetLoading, assets::load_shaders)
+            .add_system_to_stage(
+                RenderStage,
+                systems::reset_render_stats.label(RenderSystem::Stats),
+            )
+            .add_system_to_stage(
+                RenderStage,
+                camera::gather_cameras
+                    .label(RenderSystem::Cameras)
+                    .after(RenderSystem::Stats),
+            )
+            .add_system_to_stage(RenderStage, systems::upload_dynamic_meshes)
+            // Exclusive systems at the end of the stage run after the ones above
+            .add_system_to_stage(
+                RenderStage,
+                systems::render_cameras
+                    .exclusive_system()
+                    .at_end()
+                    .label(RenderSystem::Draw),
+            )
+            .add_system_to_stage(
+                RenderStage,
+                systems::text_render_pass
+                    .exclusive_system()
+                    .at_end()
+                    .label(RenderSystem::Text)
+                    .after(RenderSystem::Draw),
+            );
+
+        let mut passes = app
+            .world
+            .get_resource_or_insert_with(systems::CameraPasses::default);
+        passes.add(RenderPass::Quad, systems::QuadPass::default());
+        passes.add(RenderPass::Shape, systems::ShapePass::default());
     }
 }
 
@@ -212,24 +1386,38 @@ struct Stage {
     start_time: f64,
     active_frame_input: FrameInput,
     last_frame_input: FrameInput,
+    /// Typed since the last update
+    characters: Vec<ReceivedCharacter>,
+    /// Touch reported as the mouse when `InputSettings::touch_emulates_mouse` is on
+    mouse_touch: Option<u64>,
 }
 
 impl Stage {
     pub fn new(mut app: App, frame_input: FrameInput) -> Self {
-        let pipeline = {
+        let (pipeline, sprite_quad, shape_pipeline, text_pipeline) = {
             let mut ctx = app
                 .world
                 .get_resource_mut::<miniquad::Context>()
                 .expect("Context MUST be in the App Resources");
-            shaders::quad::build(&mut ctx)
+            (
+                shaders::quad::build(&mut ctx),
+                sprite::SpriteQuad(components::SimpleMesh::quad(&mut ctx, Vec2::ONE)),
+                shaders::shape::build(&mut ctx),
+                shaders::text::build(&mut ctx),
+            )
         };
         app.insert_resource(pipeline);
+        app.insert_resource(sprite_quad);
+        app.insert_resource(shape_pipeline);
+        app.insert_resource(text_pipeline);
 
         Self {
             app,
             start_time: miniquad::date::now(),
             active_frame_input: frame_input,
             last_frame_input: frame_input,
+            characters: Vec::new(),
+            mouse_touch: None,
         }
     }
 }
@@ -245,6 +1433,13 @@ impl Stage {
             miniquad::date::now() - self.start_time;
         self.active_frame_input.time.last_frame_time = miniquad::date::now();
         self.active_frame_input.long_state();
+
+        let mut characters = self
+            .app
+            .world
+            .get_resource_mut::<Events<ReceivedCharacter>>()
+            .unwrap();
+        characters.extend(self.characters.drain(..));
     }
 }
 
@@ -276,13 +1471,15 @@ impl EventHandlerFree for Stage {
     // Mouse Events
 
     fn mouse_motion_event(&mut self, x: f32, y: f32) {
-        self.active_frame_input.mouse.pos = Vec2::new(x, y);
+        self.active_frame_input.mouse.move_to(Vec2::new(x, y));
     }
 
-    fn mouse_wheel_event(&mut self, _x: f32, _y: f32) {}
+    fn mouse_wheel_event(&mut self, x: f32, y: f32) {
+        self.active_frame_input.mouse.wheel += Vec2::new(x, y);
+    }
 
     fn mouse_button_down_event(&mut self, button: MouseButton, x: f32, y: f32) {
-        self.active_frame_input.mouse.pos = Vec2::new(x, y);
+        self.active_frame_input.mouse.move_to(Vec2::new(x, y));
         match button {
             MouseButton::Left => self.active_frame_input.mouse.left = ButtonState::JustDown,
             MouseButton::Right => self.active_frame_input.mouse.right = ButtonState::JustDown,
@@ -292,7 +1489,7 @@ impl EventHandlerFree for Stage {
     }
 
     fn mouse_button_up_event(&mut self, button: MouseButton, x: f32, y: f32) {
-        self.active_frame_input.mouse.pos = Vec2::new(x, y);
+        self.active_frame_input.mouse.move_to(Vec2::new(x, y));
         match button {
             MouseButton::Left => self.active_frame_input.mouse.left = ButtonState::JustUp,
             MouseButton::Right => self.active_frame_input.mouse.right = ButtonState::JustUp,
@@ -301,39 +1498,70 @@ impl EventHandlerFree for Stage {
         };
     }
 
-    fn raw_mouse_motion(&mut self, _dx: f32, _dy: f32) {}
+    fn raw_mouse_motion(&mut self, dx: f32, dy: f32) {
+        self.active_frame_input.mouse.raw_motion += Vec2::new(dx, dy);
+    }
 
     // Keyboard Events
-    fn char_event(&mut self, _character: char, _keymods: KeyMods, _repeat: bool) {}
-
-    fn key_down_event(&mut self, keycode: KeyCode, _keymods: KeyMods, _repeat: bool) {
-        if let Some(keyboard_key) = maps::map_to_keyboard_key(keycode) {
-            self.active_frame_input
-                .keyboard
-                .set(keyboard_key, ButtonState::JustDown);
+    fn char_event(&mut self, character: char, keymods: KeyMods, repeat: bool) {
+        let received = ReceivedCharacter {
+            character,
+            modifiers: maps::map_to_modifiers(keymods),
+            repeat,
+        };
+        if received.is_text() {
+            self.characters.push(received);
         }
     }
 
-    fn key_up_event(&mut self, keycode: KeyCode, _keymods: KeyMods) {
-        if let Some(keyboard_key) = maps::map_to_keyboard_key(keycode) {
-            self.active_frame_input
-                .keyboard
-                .set(keyboard_key, ButtonState::JustUp);
-        }
+    fn key_down_event(&mut self, keycode: KeyCode, keymods: KeyMods, repeat: bool) {
+        self.active_frame_input.keyboard.press(
+            maps::map_to_keyboard_key(keycode),
+            maps::map_to_modifiers(keymods),
+            repeat,
+        );
+    }
+
+    fn key_up_event(&mut self, keycode: KeyCode, keymods: KeyMods) {
+        self.active_frame_input.keyboard.release(
+            maps::map_to_keyboard_key(keycode),
+            maps::map_to_modifiers(keymods),
+        );
     }
 
     // Touch Events
-    fn touch_event(&mut self, phase: TouchPhase, _id: u64, x: f32, y: f32) {
-        if phase == TouchPhase::Started {
-            self.mouse_button_down_event(MouseButton::Left, x, y);
-        }
+    fn touch_event(&mut self, phase: TouchPhase, id: u64, x: f32, y: f32) {
+        let phase = maps::map_to_touch_phase(phase);
+        let touch = self
+            .active_frame_input
+            .touches
+            .update(id, Vec2::new(x, y), phase);
 
-        if phase == TouchPhase::Ended {
-            self.mouse_button_up_event(MouseButton::Left, x, y);
+        let emulates_mouse = self
+            .app
+            .world
+            .get_resource::<InputSettings>()
+            .is_some_and(|settings| settings.touch_emulates_mouse);
+        if !emulates_mouse || touch.is_none() {
+            return;
         }
-
-        if phase == TouchPhase::Moved {
-            self.mouse_motion_event(x, y);
+        match phase {
+            input::TouchPhase::Started if self.mouse_touch.is_none() => {
+                self.mouse_touch = Some(id);
+                // A new touch lands anywhere, it does not drag the cursor there
+                self.active_frame_input.mouse.jump_to(Vec2::new(x, y));
+                self.mouse_button_down_event(MouseButton::Left, x, y);
+            }
+            input::TouchPhase::Moved if self.mouse_touch == Some(id) => {
+                self.mouse_motion_event(x, y);
+            }
+            input::TouchPhase::Ended | input::TouchPhase::Cancelled
+                if self.mouse_touch == Some(id) =>
+            {
+                self.mouse_touch = None;
+                self.mouse_button_up_event(MouseButton::Left, x, y);
+            }
+            _ => {}
         }
     }
 }
@@ -1,19 +1,71 @@
-use bevy_math::{Vec2, Vec3};
+use bevy_math::{Vec2, Vec3, Vec4};
 
-#[derive(Debug, PartialEq, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
 #[repr(C)]
 pub struct Vertex {
     pub position: Vec3,
     pub uv: Vec2,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[repr(C)]
+pub struct ColorVertex {
+    pub position: Vec3,
+    pub color: Vec4,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[repr(C)]
+pub struct TextVertex {
+    pub position: Vec3,
+    pub uv: Vec2,
+    pub color: Vec4,
+}
+
+/// GLSL sources of a shader asset, a single file holding both stages, each one starting after a
+/// `#shader vertex` or `#shader fragment` line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShaderSource {
+    pub vertex: String,
+    pub fragment: String,
+    /// Optional stage after a `#shader instanced` line, the vertex shader of instanced pipelines
+    pub instanced_vertex: Option<String>,
+}
+
+impl ShaderSource {
+    pub fn parse(source: &str) -> Result<Self, &'static str> {
+        let (mut vertex, mut fragment, mut instanced_vertex) = (None, None, None);
+        let mut current: Option<&mut String> = None;
+        for line in source.lines() {
+            match line.trim() {
+                "#shader vertex" => current = Some(vertex.insert(String::new())),
+                "#shader fragment" => current = Some(fragment.insert(String::new())),
+                "#shader instanced" => current = Some(instanced_vertex.insert(String::new())),
+                _ => {
+                    if let Some(stage) = current.as_mut() {
+                        stage.push_str(line);
+                        stage.push('\n');
+                    }
+                }
+            }
+        }
+        Ok(Self {
+            vertex: vertex.ok_or("missing `#shader vertex` stage")?,
+            fragment: fragment.ok_or("missing `#shader fragment` stage")?,
+            instanced_vertex,
+        })
+    }
+}
+
 pub mod quad {
     use std::ops::Deref;
 
     use bevy_math::{Mat4, Vec4};
     use miniquad::*;
 
-    use crate::color::Color;
+    use crate::{color::Color, mq::components::BlendMode};
+
+    use super::ShaderSource;
 
     pub const VERTEX: &str = r#"#version 100
     attribute vec3 position;
@@ -22,6 +74,7 @@ pub mod quad {
     uniform vec4 InColor;
     uniform mat4 Model;
     uniform mat4 Projection;
+    uniform vec4 UvRect;
 
     varying lowp vec4 color;
     varying lowp vec2 texcoord;
@@ -29,18 +82,42 @@ pub mod quad {
     void main() {
         gl_Position = Projection * Model * vec4(position, 1);
         color = InColor;
-        texcoord = uv;
+        texcoord = UvRect.xy + uv * UvRect.zw;
+    }
+    "#;
+
+    /// Same as `VERTEX` with the model matrix, color and uv rect read per instance
+    pub const INSTANCED_VERTEX: &str = r#"#version 100
+    attribute vec3 position;
+    attribute vec2 uv;
+    attribute mat4 model;
+    attribute vec4 color0;
+    attribute vec4 uv_rect;
+
+    uniform mat4 Projection;
+
+    varying lowp vec4 color;
+    varying lowp vec2 texcoord;
+
+    void main() {
+        gl_Position = Projection * model * vec4(position, 1);
+        color = color0;
+        texcoord = uv_rect.xy + uv * uv_rect.zw;
     }
     "#;
 
+    /// Multiplies the color by its alpha when `Premultiply` is 1.0, see `BlendMode::premultiplies`
     pub const FRAGMENT: &str = r#"#version 100
     varying lowp vec4 color;
     varying lowp vec2 texcoord;
 
     uniform sampler2D tex;
+    uniform lowp float Premultiply;
 
     void main() {
-        gl_FragColor = texture2D(tex, texcoord) * color;
+        lowp vec4 fragment = texture2D(tex, texcoord) * color;
+        fragment.rgb *= mix(1.0, fragment.a, Premultiply);
+        gl_FragColor = fragment;
     }
     "#;
 
@@ -51,18 +128,27 @@ pub mod quad {
                     UniformDesc::new("InColor", UniformType::Float4),
                     UniformDesc::new("Model", UniformType::Mat4),
                     UniformDesc::new("Projection", UniformType::Mat4),
+                    UniformDesc::new("UvRect", UniformType::Float4),
+                    UniformDesc::new("Premultiply", UniformType::Float1),
                 ],
             },
             images: vec!["tex".to_string()],
         }
     }
 
+    /// Uv rect sampling the whole texture, packed as `(u, v, width, height)`
+    pub const FULL_UV_RECT: [f32; 4] = [0.0, 0.0, 1.0, 1.0];
+
     #[repr(C)]
     #[derive(Debug)]
     pub struct Uniform {
         pub color: Vec4,
         pub model: Mat4,
         pub projection: Mat4,
+        /// Texture region the mesh uvs are mapped to
+        pub uv_rect: Vec4,
+        /// 1.0 when the blend mode expects a premultiplied color
+        pub premultiply: f32,
     }
 
     impl Default for Uniform {
@@ -71,26 +157,187 @@ pub mod quad {
                 color: Color::ANTIQUE_WHITE.into(),
                 model: Mat4::IDENTITY,
                 projection: Mat4::IDENTITY,
+                uv_rect: FULL_UV_RECT.into(),
+                premultiply: 0.0,
             }
         }
     }
 
+    pub fn instanced_meta() -> ShaderMeta {
+        ShaderMeta {
+            uniforms: UniformBlockLayout {
+                uniforms: vec![
+                    UniformDesc::new("Projection", UniformType::Mat4),
+                    UniformDesc::new("Premultiply", UniformType::Float1),
+                ],
+            },
+            images: vec!["tex".to_string()],
+        }
+    }
+
+    #[repr(C)]
+    #[derive(Debug)]
+    pub struct InstancedUniform {
+        pub projection: Mat4,
+        /// See `Uniform::premultiply`
+        pub premultiply: f32,
+    }
+
+    /// Per entity data of the instanced pipeline
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub struct QuadInstance {
+        pub model: Mat4,
+        pub color: Vec4,
+        pub uv_rect: Vec4,
+    }
+
     fn build_shader(ctx: &mut miniquad::Context) -> Shader {
         Shader::new(ctx, VERTEX, FRAGMENT, meta()).unwrap()
     }
 
-    pub struct QuadPipeline(pub Pipeline);
+    fn build_instanced_shader(ctx: &mut miniquad::Context) -> Shader {
+        Shader::new(ctx, INSTANCED_VERTEX, FRAGMENT, instanced_meta()).unwrap()
+    }
+
+    /// Maximum number of instances drawn by a single instanced draw call
+    pub const MAX_INSTANCES: usize = 1024;
+
+    /// Both quad pipelines for a single primitive type and blend mode
+    pub struct QuadPipelines {
+        /// One draw call per entity, with the model and color as uniforms
+        pub single: Pipeline,
+        /// One draw call per mesh and texture pair, with the model and color per instance
+        pub instanced: Pipeline,
+    }
+
+    pub struct QuadPipeline {
+        /// Indexed by `BlendMode as usize`
+        pub triangles: Vec<QuadPipelines>,
+        /// Indexed by `BlendMode as usize`
+        pub lines: Vec<QuadPipelines>,
+        pub instance_buffer: Buffer,
+        /// Bound for meshes without a `SimpleMeshTexture`
+        pub white_texture: Texture,
+    }
+
+    impl QuadPipelines {
+        fn new(
+            ctx: &mut miniquad::Context,
+            shader: Shader,
+            instanced_shader: Shader,
+            primitive_type: PrimitiveType,
+            blend_mode: BlendMode,
+        ) -> Self {
+            Self {
+                single: pipeline_with_shader(ctx, shader, primitive_type, blend_mode),
+                instanced: instanced_pipeline_with_shader(
+                    ctx,
+                    instanced_shader,
+                    primitive_type,
+                    blend_mode,
+                ),
+            }
+        }
+
+        /// A variant for every `BlendMode`, in `BlendMode::ALL` order
+        fn variants(
+            ctx: &mut miniquad::Context,
+            shader: Shader,
+            instanced_shader: Shader,
+            primitive_type: PrimitiveType,
+        ) -> Vec<Self> {
+            BlendMode::ALL
+                .iter()
+                .map(|&blend_mode| {
+                    Self::new(ctx, shader, instanced_shader, primitive_type, blend_mode)
+                })
+                .collect()
+        }
+    }
+
+    impl QuadPipeline {
+        /// Recompiles every pipeline from `source`, `INSTANCED_VERTEX` is kept when it has no
+        /// instanced stage. The pipelines are left untouched when a stage fails to compile.
+        pub fn reload(
+            &mut self,
+            ctx: &mut miniquad::Context,
+            source: &ShaderSource,
+        ) -> Result<(), ShaderError> {
+            let shader = Shader::new(ctx, &source.vertex, &source.fragment, meta())?;
+            // miniquad binds a program once it links and has no way to delete a `Shader`
+            let mut program = 0;
+            unsafe { gl::glGetIntegerv(gl::GL_CURRENT_PROGRAM, &mut program) };
+
+            let instanced_vertex = source
+                .instanced_vertex
+                .as_deref()
+                .unwrap_or(INSTANCED_VERTEX);
+            let instanced_shader =
+                match Shader::new(ctx, instanced_vertex, &source.fragment, instanced_meta()) {
+                    Ok(shader) => shader,
+                    Err(error) => {
+                        unsafe { gl::glDeleteProgram(program as u32) };
+                        return Err(error);
+                    }
+                };
+
+            self.triangles =
+                QuadPipelines::variants(ctx, shader, instanced_shader, PrimitiveType::Triangles);
+            self.lines =
+                QuadPipelines::variants(ctx, shader, instanced_shader, PrimitiveType::Lines);
+            Ok(())
+        }
+
+        pub fn variant(
+            &self,
+            primitive_type: PrimitiveType,
+            blend_mode: BlendMode,
+        ) -> &QuadPipelines {
+            let variants = match primitive_type {
+                PrimitiveType::Triangles => &self.triangles,
+                PrimitiveType::Lines => &self.lines,
+            };
+            &variants[blend_mode as usize]
+        }
+    }
+
     impl Deref for QuadPipeline {
         type Target = miniquad::Pipeline;
 
         fn deref(&self) -> &Self::Target {
-            &self.0
+            &self
+                .variant(PrimitiveType::Triangles, BlendMode::Opaque)
+                .single
         }
     }
 
-    pub fn pipeline(ctx: &mut miniquad::Context) -> Pipeline {
+    fn params(primitive_type: PrimitiveType, blend_mode: BlendMode) -> PipelineParams {
+        let (color_blend, alpha_blend) = blend_mode.blend_states();
+        PipelineParams {
+            primitive_type,
+            color_blend,
+            alpha_blend,
+            ..Default::default()
+        }
+    }
+
+    pub fn pipeline(
+        ctx: &mut miniquad::Context,
+        primitive_type: PrimitiveType,
+        blend_mode: BlendMode,
+    ) -> Pipeline {
         let shader = build_shader(ctx);
-        Pipeline::new(
+        pipeline_with_shader(ctx, shader, primitive_type, blend_mode)
+    }
+
+    fn pipeline_with_shader(
+        ctx: &mut miniquad::Context,
+        shader: Shader,
+        primitive_type: PrimitiveType,
+        blend_mode: BlendMode,
+    ) -> Pipeline {
+        Pipeline::with_params(
             ctx,
             &[BufferLayout::default()],
             &[
@@ -98,10 +345,329 @@ pub mod quad {
                 VertexAttribute::new("uv", VertexFormat::Float2),
             ],
             shader,
+            params(primitive_type, blend_mode),
+        )
+    }
+
+    pub fn instanced_pipeline(
+        ctx: &mut miniquad::Context,
+        primitive_type: PrimitiveType,
+        blend_mode: BlendMode,
+    ) -> Pipeline {
+        let shader = build_instanced_shader(ctx);
+        instanced_pipeline_with_shader(ctx, shader, primitive_type, blend_mode)
+    }
+
+    fn instanced_pipeline_with_shader(
+        ctx: &mut miniquad::Context,
+        shader: Shader,
+        primitive_type: PrimitiveType,
+        blend_mode: BlendMode,
+    ) -> Pipeline {
+        Pipeline::with_params(
+            ctx,
+            &[
+                BufferLayout::default(),
+                BufferLayout {
+                    step_func: VertexStep::PerInstance,
+                    ..Default::default()
+                },
+            ],
+            &[
+                VertexAttribute::with_buffer("position", VertexFormat::Float3, 0),
+                VertexAttribute::with_buffer("uv", VertexFormat::Float2, 0),
+                VertexAttribute::with_buffer("model", VertexFormat::Mat4, 1),
+                VertexAttribute::with_buffer("color0", VertexFormat::Float4, 1),
+                VertexAttribute::with_buffer("uv_rect", VertexFormat::Float4, 1),
+            ],
+            shader,
+            params(primitive_type, blend_mode),
         )
     }
 
     pub fn build(ctx: &mut miniquad::Context) -> QuadPipeline {
-        QuadPipeline(pipeline(ctx))
+        let instance_buffer = Buffer::stream(
+            ctx,
+            BufferType::VertexBuffer,
+            MAX_INSTANCES * std::mem::size_of::<QuadInstance>(),
+        );
+        let shader = build_shader(ctx);
+        let instanced_shader = build_instanced_shader(ctx);
+        QuadPipeline {
+            triangles: QuadPipelines::variants(
+                ctx,
+                shader,
+                instanced_shader,
+                PrimitiveType::Triangles,
+            ),
+            lines: QuadPipelines::variants(ctx, shader, instanced_shader, PrimitiveType::Lines),
+            instance_buffer,
+            white_texture: Texture::from_rgba8(ctx, 1, 1, &[0xFF; 4]),
+        }
+    }
+}
+
+pub mod shape {
+    use std::ops::Deref;
+
+    use bevy_math::Mat4;
+    use miniquad::*;
+
+    use super::ColorVertex;
+
+    /// Maximum number of vertices flushed in a single shape draw call
+    pub const MAX_VERTICES: usize = 10_000;
+    /// Maximum number of indices flushed in a single shape draw call
+    pub const MAX_INDICES: usize = 15_000;
+
+    pub const VERTEX: &str = r#"#version 100
+    attribute vec3 position;
+    attribute vec4 color0;
+
+    uniform mat4 Projection;
+
+    varying lowp vec4 color;
+
+    void main() {
+        gl_Position = Projection * vec4(position, 1);
+        color = color0;
+    }
+    "#;
+
+    pub const FRAGMENT: &str = r#"#version 100
+    varying lowp vec4 color;
+
+    void main() {
+        gl_FragColor = color;
+    }
+    "#;
+
+    pub fn meta() -> ShaderMeta {
+        ShaderMeta {
+            uniforms: UniformBlockLayout {
+                uniforms: vec![UniformDesc::new("Projection", UniformType::Mat4)],
+            },
+            images: vec![],
+        }
+    }
+
+    #[repr(C)]
+    #[derive(Debug)]
+    pub struct Uniform {
+        pub projection: Mat4,
+    }
+
+    impl Default for Uniform {
+        fn default() -> Self {
+            Self {
+                projection: Mat4::IDENTITY,
+            }
+        }
+    }
+
+    fn build_shader(ctx: &mut miniquad::Context) -> Shader {
+        Shader::new(ctx, VERTEX, FRAGMENT, meta()).unwrap()
+    }
+
+    /// Pipeline and streaming buffers used to flush the `DebugShape2D` batches
+    pub struct ShapePipeline {
+        pub pipeline: Pipeline,
+        pub bindings: Bindings,
+    }
+
+    impl Deref for ShapePipeline {
+        type Target = miniquad::Pipeline;
+
+        fn deref(&self) -> &Self::Target {
+            &self.pipeline
+        }
+    }
+
+    pub fn pipeline(ctx: &mut miniquad::Context) -> Pipeline {
+        let shader = build_shader(ctx);
+        Pipeline::with_params(
+            ctx,
+            &[BufferLayout::default()],
+            &[
+                VertexAttribute::new("position", VertexFormat::Float3),
+                VertexAttribute::new("color0", VertexFormat::Float4),
+            ],
+            shader,
+            PipelineParams {
+                color_blend: Some(BlendState::new(
+                    Equation::Add,
+                    BlendFactor::Value(BlendValue::SourceAlpha),
+                    BlendFactor::OneMinusValue(BlendValue::SourceAlpha),
+                )),
+                ..Default::default()
+            },
+        )
+    }
+
+    pub fn build(ctx: &mut miniquad::Context) -> ShapePipeline {
+        let vertex_buffer = Buffer::stream(
+            ctx,
+            BufferType::VertexBuffer,
+            MAX_VERTICES * std::mem::size_of::<ColorVertex>(),
+        );
+        let index_buffer = Buffer::stream(
+            ctx,
+            BufferType::IndexBuffer,
+            MAX_INDICES * std::mem::size_of::<u16>(),
+        );
+        ShapePipeline {
+            pipeline: pipeline(ctx),
+            bindings: Bindings {
+                vertex_buffers: vec![vertex_buffer],
+                index_buffer,
+                images: vec![],
+            },
+        }
+    }
+}
+
+pub mod text {
+    use std::ops::Deref;
+
+    use bevy_math::Mat4;
+    use miniquad::*;
+
+    use super::TextVertex;
+
+    /// Maximum number of glyphs flushed in a single text draw call
+    pub const MAX_GLYPHS: usize = 2048;
+
+    pub const VERTEX: &str = r#"#version 100
+    attribute vec3 position;
+    attribute vec2 uv;
+    attribute vec4 color0;
+
+    uniform mat4 Projection;
+
+    varying lowp vec4 color;
+    varying lowp vec2 texcoord;
+
+    void main() {
+        gl_Position = Projection * vec4(position, 1);
+        color = color0;
+        texcoord = uv;
+    }
+    "#;
+
+    pub const FRAGMENT: &str = r#"#version 100
+    varying lowp vec4 color;
+    varying lowp vec2 texcoord;
+
+    uniform sampler2D tex;
+
+    void main() {
+        gl_FragColor = texture2D(tex, texcoord) * color;
+    }
+    "#;
+
+    pub fn meta() -> ShaderMeta {
+        ShaderMeta {
+            uniforms: UniformBlockLayout {
+                uniforms: vec![UniformDesc::new("Projection", UniformType::Mat4)],
+            },
+            images: vec!["tex".to_string()],
+        }
+    }
+
+    #[repr(C)]
+    #[derive(Debug)]
+    pub struct Uniform {
+        pub projection: Mat4,
+    }
+
+    impl Default for Uniform {
+        fn default() -> Self {
+            Self {
+                projection: Mat4::IDENTITY,
+            }
+        }
+    }
+
+    fn build_shader(ctx: &mut miniquad::Context) -> Shader {
+        Shader::new(ctx, VERTEX, FRAGMENT, meta()).unwrap()
+    }
+
+    /// Pipeline and streaming buffers used to flush the `DebugText` glyphs
+    pub struct TextPipeline {
+        pub pipeline: Pipeline,
+        pub bindings: Bindings,
+    }
+
+    impl Deref for TextPipeline {
+        type Target = miniquad::Pipeline;
+
+        fn deref(&self) -> &Self::Target {
+            &self.pipeline
+        }
+    }
+
+    pub fn pipeline(ctx: &mut miniquad::Context) -> Pipeline {
+        let shader = build_shader(ctx);
+        Pipeline::with_params(
+            ctx,
+            &[BufferLayout::default()],
+            &[
+                VertexAttribute::new("position", VertexFormat::Float3),
+                VertexAttribute::new("uv", VertexFormat::Float2),
+                VertexAttribute::new("color0", VertexFormat::Float4),
+            ],
+            shader,
+            PipelineParams {
+                color_blend: Some(BlendState::new(
+                    Equation::Add,
+                    BlendFactor::Value(BlendValue::SourceAlpha),
+                    BlendFactor::OneMinusValue(BlendValue::SourceAlpha),
+                )),
+                ..Default::default()
+            },
+        )
+    }
+
+    pub fn build(ctx: &mut miniquad::Context) -> TextPipeline {
+        let vertex_buffer = Buffer::stream(
+            ctx,
+            BufferType::VertexBuffer,
+            MAX_GLYPHS * 4 * std::mem::size_of::<TextVertex>(),
+        );
+        let index_buffer = Buffer::stream(
+            ctx,
+            BufferType::IndexBuffer,
+            MAX_GLYPHS * 6 * std::mem::size_of::<u16>(),
+        );
+        TextPipeline {
+            pipeline: pipeline(ctx),
+            bindings: Bindings {
+                vertex_buffers: vec![vertex_buffer],
+                index_buffer,
+                images: vec![],
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shader_source_is_split_in_stages() {
+        let source =
+            "#shader vertex\n#version 100\nvoid main() {}\n#shader fragment\n#version 100\n";
+        let shader = ShaderSource::parse(source).unwrap();
+        assert_eq!(shader.vertex, "#version 100\nvoid main() {}\n");
+        assert_eq!(shader.fragment, "#version 100\n");
+        assert_eq!(shader.instanced_vertex, None);
+
+        let shader =
+            ShaderSource::parse(&format!("{}#shader instanced\nvoid main() {{}}\n", source))
+                .unwrap();
+        assert_eq!(shader.instanced_vertex.as_deref(), Some("void main() {}\n"));
+
+        assert!(ShaderSource::parse("#shader vertex\n").is_err());
     }
 }
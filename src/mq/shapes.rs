@@ -1,26 +1,249 @@
-// TODO(JryanH): I don't have the same pipeline as Macroquad, so I think instead I need to draw to a specific camera?
 use crate::prelude::*;
 
-#[derive(Debug, Clone, Copy, Default)]
-pub struct DebugShape2D;
+use super::shaders::{
+    shape::{MAX_INDICES, MAX_VERTICES},
+    ColorVertex,
+};
+
+/// Number of sides used to approximate a circle
+pub const CIRCLE_SEGMENTS: u8 = 32;
 
 pub enum DrawType {
     Solid { color: Color },
     Outline { thickness: f32, color: Color },
 }
 
+/// Geometry that fits in a single shape draw call
+#[derive(Debug, Clone, Default)]
+pub struct ShapeBatch {
+    pub vertices: Vec<ColorVertex>,
+    pub indices: Vec<u16>,
+}
+
+/// Immediate mode shapes, drawn with the active `Projection` during the `RenderStage`
+/// and cleared afterwards.
+#[derive(Debug, Clone, Default)]
+pub struct DebugShape2D {
+    batches: Vec<ShapeBatch>,
+}
+
 impl DebugShape2D {
-    pub fn draw_circle(&mut self, _center: Vec2, _radius: f32, _draw_type: DrawType) {}
+    pub fn draw_circle(&mut self, center: Vec2, radius: f32, draw_type: DrawType) {
+        self.draw_poly(center, CIRCLE_SEGMENTS, radius, 0.0, draw_type);
+    }
+
+    /// Draws a regular polygon, `rotation` is in degrees
     pub fn draw_poly(
         &mut self,
-        _center: Vec2,
-        _sides: u8,
-        _radius: f32,
-        _rotation: f32,
-        _draw_type: DrawType,
+        center: Vec2,
+        sides: u8,
+        radius: f32,
+        rotation: f32,
+        draw_type: DrawType,
     ) {
+        if sides < 3 {
+            return;
+        }
+        let outer = poly_points(center, sides, radius, rotation);
+        match draw_type {
+            DrawType::Solid { color } => {
+                let mut positions = Vec::with_capacity(outer.len() + 1);
+                positions.push(center);
+                positions.extend_from_slice(&outer);
+                let indices = fan_indices(outer.len() as u16);
+                self.push(&positions, &indices, color);
+            }
+            DrawType::Outline { thickness, color } => {
+                let inner = poly_points(center, sides, (radius - thickness).max(0.0), rotation);
+                self.push_ring(&outer, &inner, color);
+            }
+        }
+    }
+
+    /// Draws an axis aligned rectangle spanning `center - extends` to `center + extends`
+    pub fn draw_rectangle(&mut self, center: Vec2, extends: Vec2, draw_type: DrawType) {
+        let outer = rectangle_points(center, extends);
+        match draw_type {
+            DrawType::Solid { color } => self.push(&outer, &[0, 1, 2, 0, 2, 3], color),
+            DrawType::Outline { thickness, color } => {
+                let inner =
+                    rectangle_points(center, (extends - Vec2::splat(thickness)).max(Vec2::ZERO));
+                self.push_ring(&outer, &inner, color);
+            }
+        }
+    }
+
+    pub fn draw_triangle(&mut self, v1: Vec2, v2: Vec2, v3: Vec2, draw_type: DrawType) {
+        match draw_type {
+            DrawType::Solid { color } => self.push(&[v1, v2, v3], &[0, 1, 2], color),
+            DrawType::Outline { thickness, color } => {
+                self.draw_line(v1, v2, thickness, color);
+                self.draw_line(v2, v3, thickness, color);
+                self.draw_line(v3, v1, thickness, color);
+            }
+        }
+    }
+
+    pub fn draw_line(&mut self, start: Vec2, end: Vec2, thickness: f32, color: Color) {
+        let direction = end - start;
+        if direction.length_squared() <= f32::EPSILON {
+            return;
+        }
+        let normal = direction.perp().normalize() * (thickness / 2.0);
+        self.push(
+            &[start + normal, end + normal, end - normal, start - normal],
+            &[0, 1, 2, 0, 2, 3],
+            color,
+        );
+    }
+
+    pub fn batches(&self) -> &[ShapeBatch] {
+        &self.batches
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.batches.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.batches.clear();
+    }
+
+    /// Fills the space between two closed outlines with the same number of points
+    fn push_ring(&mut self, outer: &[Vec2], inner: &[Vec2], color: Color) {
+        debug_assert_eq!(outer.len(), inner.len());
+        let count = outer.len() as u16;
+        let mut positions = Vec::with_capacity(outer.len() * 2);
+        positions.extend_from_slice(outer);
+        positions.extend_from_slice(inner);
+        let indices: Vec<u16> = (0..count)
+            .flat_map(|i| {
+                let next = (i + 1) % count;
+                [i, next, count + next, i, count + next, count + i]
+            })
+            .collect();
+        self.push(&positions, &indices, color);
+    }
+
+    fn push(&mut self, positions: &[Vec2], indices: &[u16], color: Color) {
+        let fits = self.batches.last().is_some_and(|batch| {
+            batch.vertices.len() + positions.len() <= MAX_VERTICES
+                && batch.indices.len() + indices.len() <= MAX_INDICES
+        });
+        if !fits {
+            self.batches.push(ShapeBatch::default());
+        }
+        let batch = self.batches.last_mut().expect("A batch was just pushed");
+
+        let offset = batch.vertices.len() as u16;
+        let color: Vec4 = color.into();
+        batch
+            .vertices
+            .extend(positions.iter().map(|position| ColorVertex {
+                position: position.extend(0.0),
+                color,
+            }));
+        batch
+            .indices
+            .extend(indices.iter().map(|index| index + offset));
+    }
+}
+
+fn poly_points(center: Vec2, sides: u8, radius: f32, rotation: f32) -> Vec<Vec2> {
+    let rotation = rotation.to_radians();
+    let step = std::f32::consts::TAU / sides as f32;
+    (0..sides)
+        .map(|side| {
+            let angle = rotation + step * side as f32;
+            center + Vec2::new(angle.cos(), angle.sin()) * radius
+        })
+        .collect()
+}
+
+fn rectangle_points(center: Vec2, extends: Vec2) -> [Vec2; 4] {
+    [
+        center + Vec2::new(-extends.x, -extends.y),
+        center + Vec2::new(extends.x, -extends.y),
+        center + Vec2::new(extends.x, extends.y),
+        center + Vec2::new(-extends.x, extends.y),
+    ]
+}
+
+/// Triangle fan around vertex 0, with `count` vertices along the edge
+fn fan_indices(count: u16) -> Vec<u16> {
+    (0..count)
+        .flat_map(|i| [0, 1 + i, 1 + (i + 1) % count])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn solid_poly_is_a_fan() {
+        let mut shapes = DebugShape2D::default();
+        shapes.draw_poly(
+            Vec2::ZERO,
+            6,
+            1.0,
+            0.0,
+            DrawType::Solid { color: Color::RED },
+        );
+
+        let batch = &shapes.batches()[0];
+        assert_eq!(batch.vertices.len(), 7);
+        assert_eq!(batch.indices.len(), 6 * 3);
+        assert_eq!(batch.vertices[0].position, Vec3::ZERO);
+    }
+
+    #[test]
+    fn outline_rectangle_is_a_ring() {
+        let mut shapes = DebugShape2D::default();
+        shapes.draw_rectangle(
+            Vec2::ZERO,
+            Vec2::new(2.0, 1.0),
+            DrawType::Outline {
+                thickness: 0.5,
+                color: Color::RED,
+            },
+        );
+
+        let batch = &shapes.batches()[0];
+        assert_eq!(batch.vertices.len(), 8);
+        assert_eq!(batch.indices.len(), 4 * 6);
+        assert_eq!(batch.vertices[6].position, Vec3::new(1.5, 0.5, 0.0));
+    }
+
+    #[test]
+    fn indices_are_offset_within_a_batch() {
+        let mut shapes = DebugShape2D::default();
+        shapes.draw_line(Vec2::ZERO, Vec2::X, 1.0, Color::RED);
+        shapes.draw_line(Vec2::ZERO, Vec2::Y, 1.0, Color::RED);
+
+        let batch = &shapes.batches()[0];
+        assert_eq!(&batch.indices[6..], &[4, 5, 6, 4, 6, 7]);
+    }
+
+    #[test]
+    fn degenerate_line_is_skipped() {
+        let mut shapes = DebugShape2D::default();
+        shapes.draw_line(Vec2::ONE, Vec2::ONE, 1.0, Color::RED);
+        assert!(shapes.is_empty());
+    }
+
+    #[test]
+    fn full_batch_starts_a_new_one() {
+        let mut shapes = DebugShape2D::default();
+        let lines = MAX_VERTICES / 4 + 1;
+        for _ in 0..lines {
+            shapes.draw_line(Vec2::ZERO, Vec2::X, 1.0, Color::RED);
+        }
+
+        assert_eq!(shapes.batches().len(), 2);
+        assert_eq!(shapes.batches()[1].indices, vec![0, 1, 2, 0, 2, 3]);
+
+        shapes.clear();
+        assert!(shapes.is_empty());
     }
-    pub fn draw_rectangle(&mut self, _center: Vec2, _extends: Vec2, _draw_type: DrawType) {}
-    pub fn draw_triangle(&mut self, _v1: Vec2, _v2: Vec2, _v3: Vec2, _draw_type: DrawType) {}
-    pub fn draw_line(&mut self, _start: Vec2, _end: Vec2, _thickness: f32, _color: Color) {}
 }
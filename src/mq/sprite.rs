@@ -0,0 +1,336 @@
+use miniquad::Texture;
+
+use crate::prelude::*;
+
+use super::{
+    components::{SimpleMesh, SimpleMeshTexture},
+    Assets, Handle,
+};
+
+/// Axis aligned rectangle, in texture pixels when used as a source rect
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Rect {
+    pub min: Vec2,
+    pub max: Vec2,
+}
+
+impl Rect {
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self {
+            min: Vec2::new(x, y),
+            max: Vec2::new(x + width, y + height),
+        }
+    }
+
+    pub fn size(&self) -> Vec2 {
+        self.max - self.min
+    }
+}
+
+/// Point of the sprite placed at its `Transform` translation
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Anchor {
+    #[default]
+    Center,
+    BottomLeft,
+    BottomCenter,
+    BottomRight,
+    CenterLeft,
+    CenterRight,
+    TopLeft,
+    TopCenter,
+    TopRight,
+    /// Relative to the sprite size, `(-0.5, -0.5)` is the bottom left corner and `(0.5, 0.5)`
+    /// the top right one
+    Custom(Vec2),
+}
+
+impl Anchor {
+    pub fn as_vec(&self) -> Vec2 {
+        match self {
+            Anchor::Center => Vec2::ZERO,
+            Anchor::BottomLeft => Vec2::new(-0.5, -0.5),
+            Anchor::BottomCenter => Vec2::new(0.0, -0.5),
+            Anchor::BottomRight => Vec2::new(0.5, -0.5),
+            Anchor::CenterLeft => Vec2::new(-0.5, 0.0),
+            Anchor::CenterRight => Vec2::new(0.5, 0.0),
+            Anchor::TopLeft => Vec2::new(-0.5, 0.5),
+            Anchor::TopCenter => Vec2::new(0.0, 0.5),
+            Anchor::TopRight => Vec2::new(0.5, 0.5),
+            Anchor::Custom(point) => *point,
+        }
+    }
+}
+
+/// Texture of a `Sprite` or a `TextureAtlas`, set directly or through a handle
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SpriteTexture {
+    Texture(Texture),
+    Handle(Handle<SimpleMeshTexture>),
+}
+
+impl SpriteTexture {
+    /// `None` while the texture handle is loading
+    pub fn resolve(&self, textures: &Assets<SimpleMeshTexture>) -> Option<Texture> {
+        match self {
+            SpriteTexture::Texture(texture) => Some(*texture),
+            SpriteTexture::Handle(handle) => textures.get(handle).map(|texture| texture.0),
+        }
+    }
+}
+
+impl From<Texture> for SpriteTexture {
+    fn from(texture: Texture) -> Self {
+        SpriteTexture::Texture(texture)
+    }
+}
+
+impl From<Handle<SimpleMeshTexture>> for SpriteTexture {
+    fn from(handle: Handle<SimpleMeshTexture>) -> Self {
+        SpriteTexture::Handle(handle)
+    }
+}
+
+/// A textured quad showing the whole texture or a region of it, tinted by an optional `MeshColor`
+#[derive(Debug, Clone, Copy, Component)]
+pub struct Sprite {
+    pub texture: SpriteTexture,
+    /// Region of the texture to show, in pixels, the whole texture when `None`
+    pub rect: Option<Rect>,
+    pub flip_x: bool,
+    pub flip_y: bool,
+    pub anchor: Anchor,
+    /// Size in world units, the size of the source region in pixels when `None`
+    pub custom_size: Option<Vec2>,
+}
+
+impl Sprite {
+    pub fn new(texture: impl Into<SpriteTexture>) -> Self {
+        Self {
+            texture: texture.into(),
+            rect: None,
+            flip_x: false,
+            flip_y: false,
+            anchor: Anchor::default(),
+            custom_size: None,
+        }
+    }
+
+    /// Shows the region `index` of `atlas`
+    pub fn from_atlas(atlas: &TextureAtlas, index: usize) -> Self {
+        Self {
+            rect: atlas.region(index),
+            ..Self::new(atlas.texture)
+        }
+    }
+
+    /// The source region in pixels, `texture` being the resolved `Sprite::texture`
+    pub fn source(&self, texture: Texture) -> Rect {
+        self.rect.unwrap_or(Rect {
+            min: Vec2::ZERO,
+            max: Vec2::new(texture.width as f32, texture.height as f32),
+        })
+    }
+
+    pub fn size(&self, texture: Texture) -> Vec2 {
+        self.custom_size
+            .unwrap_or_else(|| self.source(texture).size())
+    }
+
+    /// Normalized `(offset, scale)` of the source region packed as `(u, v, width, height)`,
+    /// flipping negates the scale
+    pub fn uv_rect(&self, texture: Texture) -> Vec4 {
+        let texture_size = Vec2::new(texture.width as f32, texture.height as f32);
+        let source = self.source(texture);
+        let (mut min, mut max) = (source.min / texture_size, source.max / texture_size);
+        if self.flip_x {
+            std::mem::swap(&mut min.x, &mut max.x);
+        }
+        // Images are uploaded top row first, so v grows downwards while the quad uvs grow
+        // upwards: the bottom of the quad samples the bottom of the region unless flipped
+        if !self.flip_y {
+            std::mem::swap(&mut min.y, &mut max.y);
+        }
+        let scale = max - min;
+        Vec4::new(min.x, min.y, scale.x, scale.y)
+    }
+
+    /// Model matrix of the unit quad the sprite is drawn with
+    pub fn model(&self, transform: &Transform, texture: Texture) -> Mat4 {
+        let size = self.size(texture);
+        let offset = -self.anchor.as_vec() * size;
+        transform.compute_matrix()
+            * Mat4::from_scale_rotation_translation(
+                size.extend(1.0),
+                Quat::IDENTITY,
+                offset.extend(0.0),
+            )
+    }
+}
+
+/// Regions of a sprite sheet, in pixels
+#[derive(Debug, Clone)]
+pub struct TextureAtlas {
+    pub texture: SpriteTexture,
+    regions: Vec<Rect>,
+}
+
+impl TextureAtlas {
+    /// An atlas without regions, see `add_region`
+    pub fn new(texture: impl Into<SpriteTexture>) -> Self {
+        Self {
+            texture: texture.into(),
+            regions: Vec::new(),
+        }
+    }
+
+    /// Regions of a grid of `tile_size` cells, listed row by row from the top left.
+    ///
+    /// `padding` is the space between cells and `offset` the position of the first one.
+    pub fn from_grid(
+        texture: impl Into<SpriteTexture>,
+        tile_size: Vec2,
+        columns: usize,
+        rows: usize,
+        padding: Vec2,
+        offset: Vec2,
+    ) -> Self {
+        let mut atlas = Self::new(texture);
+        for row in 0..rows {
+            for column in 0..columns {
+                let min = offset + (tile_size + padding) * Vec2::new(column as f32, row as f32);
+                atlas.add_region(Rect {
+                    min,
+                    max: min + tile_size,
+                });
+            }
+        }
+        atlas
+    }
+
+    /// Adds a packed region and returns its index
+    pub fn add_region(&mut self, rect: Rect) -> usize {
+        self.regions.push(rect);
+        self.regions.len() - 1
+    }
+
+    pub fn region(&self, index: usize) -> Option<Rect> {
+        self.regions.get(index).copied()
+    }
+
+    pub fn regions(&self) -> &[Rect] {
+        &self.regions
+    }
+
+    pub fn len(&self) -> usize {
+        self.regions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.regions.is_empty()
+    }
+}
+
+/// Unit quad every `Sprite` is drawn with
+pub(crate) struct SpriteQuad(pub SimpleMesh);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texture(width: u32, height: u32) -> Texture {
+        let mut texture = Texture::empty();
+        texture.width = width;
+        texture.height = height;
+        texture
+    }
+
+    #[test]
+    fn grid_regions_are_listed_row_by_row() {
+        let atlas = TextureAtlas::from_grid(
+            texture(64, 64),
+            Vec2::splat(16.0),
+            3,
+            2,
+            Vec2::splat(2.0),
+            Vec2::splat(1.0),
+        );
+        assert_eq!(atlas.len(), 6);
+        assert_eq!(atlas.region(1), Some(Rect::new(19.0, 1.0, 16.0, 16.0)));
+        assert_eq!(atlas.region(3), Some(Rect::new(1.0, 19.0, 16.0, 16.0)));
+        assert_eq!(atlas.region(6), None);
+    }
+
+    #[test]
+    fn flipping_negates_the_uv_scale() {
+        let atlas = TextureAtlas::from_grid(
+            texture(64, 32),
+            Vec2::new(16.0, 16.0),
+            4,
+            2,
+            Vec2::ZERO,
+            Vec2::ZERO,
+        );
+        let mut sprite = Sprite::from_atlas(&atlas, 5);
+        let sheet = texture(64, 32);
+        assert_eq!(sprite.size(sheet), Vec2::splat(16.0));
+        assert_eq!(sprite.uv_rect(sheet), Vec4::new(0.25, 1.0, 0.25, -0.5));
+
+        sprite.flip_x = true;
+        assert_eq!(sprite.uv_rect(sheet), Vec4::new(0.5, 1.0, -0.25, -0.5));
+    }
+
+    #[test]
+    fn top_left_cell_samples_the_top_of_the_sheet() {
+        let atlas = TextureAtlas::from_grid(
+            texture(64, 32),
+            Vec2::new(16.0, 16.0),
+            4,
+            2,
+            Vec2::ZERO,
+            Vec2::ZERO,
+        );
+        let mut sprite = Sprite::from_atlas(&atlas, 0);
+        let uv_rect = sprite.uv_rect(texture(64, 32));
+        let texcoord =
+            |uv: Vec2| uv_rect.truncate().truncate() + uv * Vec2::new(uv_rect.z, uv_rect.w);
+        // First uploaded row is v = 0, the cell covers rows 0 to 16 of 32
+        assert_eq!(texcoord(Vec2::new(0.0, 1.0)), Vec2::new(0.0, 0.0));
+        assert_eq!(texcoord(Vec2::new(1.0, 0.0)), Vec2::new(0.25, 0.5));
+
+        sprite.flip_y = true;
+        assert_eq!(
+            sprite.uv_rect(texture(64, 32)),
+            Vec4::new(0.0, 0.0, 0.25, 0.5)
+        );
+    }
+
+    #[test]
+    fn texture_handles_resolve_once_loaded() {
+        let mut textures = Assets::<SimpleMeshTexture>::default();
+        let handle = textures.add(SimpleMeshTexture(texture(4, 4)));
+        let sprite = Sprite::new(handle);
+        assert_eq!(sprite.texture.resolve(&textures), Some(texture(4, 4)));
+
+        let loading = Sprite::new(textures.load("sheet.png"));
+        assert_eq!(loading.texture.resolve(&textures), None);
+    }
+
+    #[test]
+    fn anchor_is_placed_at_the_translation() {
+        let sprite = Sprite {
+            anchor: Anchor::BottomLeft,
+            custom_size: Some(Vec2::new(4.0, 2.0)),
+            ..Sprite::new(texture(8, 8))
+        };
+        let model = sprite.model(&Transform::from_xyz(1.0, 1.0, 0.0), texture(8, 8));
+        assert_eq!(
+            model.transform_point3(Vec3::new(-0.5, -0.5, 0.0)),
+            Vec3::new(1.0, 1.0, 0.0)
+        );
+        assert_eq!(
+            model.transform_point3(Vec3::new(0.5, 0.5, 0.0)),
+            Vec3::new(5.0, 3.0, 0.0)
+        );
+    }
+}
@@ -0,0 +1,264 @@
+use std::collections::HashMap;
+
+use miniquad::{Context, Texture};
+
+use super::Font;
+
+/// Identifies a rasterized glyph: the font handle, the character and the pixel size it was rasterized at
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct GlyphKey {
+    pub font: Font,
+    pub character: char,
+    pub size: u16,
+}
+
+/// Pixel rectangle of a glyph inside the atlas
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AtlasRect {
+    pub x: u16,
+    pub y: u16,
+    pub width: u16,
+    pub height: u16,
+}
+
+/// RGBA8 glyph cache packed in rows, grown by doubling whenever a glyph does not fit.
+///
+/// Growing keeps every cached glyph at the same pixel position, so only the uvs change. Once
+/// the atlas reached its maximum size it is cleared instead, and glyphs are packed again.
+pub struct GlyphAtlas {
+    width: u16,
+    height: u16,
+    max_size: u16,
+    pixels: Vec<u8>,
+    glyphs: HashMap<GlyphKey, AtlasRect>,
+    cursor_x: u16,
+    cursor_y: u16,
+    row_height: u16,
+    dirty: bool,
+    texture: Option<Texture>,
+}
+
+impl Default for GlyphAtlas {
+    fn default() -> Self {
+        Self::new(GlyphAtlas::INITIAL_SIZE, GlyphAtlas::INITIAL_SIZE)
+    }
+}
+
+impl GlyphAtlas {
+    // pixel gap between glyphs, avoids bleeding when sampling with linear filtering
+    const GAP: u16 = 1;
+    const INITIAL_SIZE: u16 = 512;
+    const MAX_SIZE: u16 = 4096;
+
+    pub fn new(width: u16, height: u16) -> Self {
+        Self {
+            width,
+            height,
+            max_size: Self::MAX_SIZE.max(width).max(height),
+            pixels: vec![0; width as usize * height as usize * 4],
+            glyphs: HashMap::new(),
+            cursor_x: 0,
+            cursor_y: 0,
+            row_height: 0,
+            dirty: true,
+            texture: None,
+        }
+    }
+
+    pub fn get(&self, key: &GlyphKey) -> Option<AtlasRect> {
+        self.glyphs.get(key).copied()
+    }
+
+    /// Normalized `(min, max)` texture coordinates of a cached glyph
+    pub fn uv(&self, key: &GlyphKey) -> Option<([f32; 2], [f32; 2])> {
+        self.get(key).map(|rect| {
+            let (width, height) = (self.width as f32, self.height as f32);
+            (
+                [rect.x as f32 / width, rect.y as f32 / height],
+                [
+                    (rect.x + rect.width) as f32 / width,
+                    (rect.y + rect.height) as f32 / height,
+                ],
+            )
+        })
+    }
+
+    /// Whether a glyph this large fits in the atlas at its maximum size
+    pub fn can_hold(&self, width: u16, height: u16) -> bool {
+        let max_size = self.max_size as u32;
+        width as u32 + Self::GAP as u32 <= max_size && height as u32 + Self::GAP as u32 <= max_size
+    }
+
+    /// Packs a glyph, `coverage` is one byte per pixel, `width * height` long. Returns `None`
+    /// for glyphs larger than the maximum size of the atlas.
+    pub fn insert(
+        &mut self,
+        key: GlyphKey,
+        width: u16,
+        height: u16,
+        coverage: &[u8],
+    ) -> Option<AtlasRect> {
+        debug_assert_eq!(coverage.len(), width as usize * height as usize);
+        if !self.can_hold(width, height) {
+            return None;
+        }
+
+        if !self.fits_horizontally(width) {
+            self.cursor_x = 0;
+            self.cursor_y += self.row_height;
+            self.row_height = 0;
+        }
+        while !self.fits_horizontally(width)
+            || self.cursor_y as u32 + height as u32 + Self::GAP as u32 > self.height as u32
+        {
+            if self.width < self.max_size || self.height < self.max_size {
+                self.grow();
+            } else {
+                self.clear();
+            }
+        }
+
+        let rect = AtlasRect {
+            x: self.cursor_x,
+            y: self.cursor_y,
+            width,
+            height,
+        };
+        for row in 0..height as usize {
+            for column in 0..width as usize {
+                let alpha = coverage[row * width as usize + column];
+                let index =
+                    ((rect.y as usize + row) * self.width as usize + rect.x as usize + column) * 4;
+                self.pixels[index..index + 4].copy_from_slice(&[0xFF, 0xFF, 0xFF, alpha]);
+            }
+        }
+
+        self.cursor_x += width + Self::GAP;
+        self.row_height = self.row_height.max(height + Self::GAP);
+        self.glyphs.insert(key, rect);
+        self.dirty = true;
+        Some(rect)
+    }
+
+    fn fits_horizontally(&self, width: u16) -> bool {
+        self.cursor_x as u32 + width as u32 + Self::GAP as u32 <= self.width as u32
+    }
+
+    /// Forgets every glyph, keeping the size
+    fn clear(&mut self) {
+        self.glyphs.clear();
+        self.pixels.iter_mut().for_each(|byte| *byte = 0);
+        self.cursor_x = 0;
+        self.cursor_y = 0;
+        self.row_height = 0;
+        self.dirty = true;
+    }
+
+    /// Uploads the pixels if anything changed since the last call, recreating the texture after growth
+    pub fn texture(&mut self, ctx: &mut Context) -> Texture {
+        let texture = match self.texture {
+            Some(texture)
+                if texture.width == self.width as u32 && texture.height == self.height as u32 =>
+            {
+                if self.dirty {
+                    texture.update(ctx, &self.pixels);
+                }
+                texture
+            }
+            stale => {
+                if let Some(texture) = stale {
+                    texture.delete();
+                }
+                Texture::from_rgba8(ctx, self.width, self.height, &self.pixels)
+            }
+        };
+        self.texture = Some(texture);
+        self.dirty = false;
+        texture
+    }
+
+    fn grow(&mut self) {
+        let (old_width, old_height) = (self.width as usize, self.height as usize);
+        let width = (self.width as u32 * 2).min(self.max_size as u32) as u16;
+        let height = (self.height as u32 * 2).min(self.max_size as u32) as u16;
+        let mut pixels = vec![0; width as usize * height as usize * 4];
+        for row in 0..old_height {
+            let old_start = row * old_width * 4;
+            let new_start = row * width as usize * 4;
+            pixels[new_start..new_start + old_width * 4]
+                .copy_from_slice(&self.pixels[old_start..old_start + old_width * 4]);
+        }
+
+        // The old rows are left as is, new glyphs start below them at the new width
+        self.cursor_x = 0;
+        self.cursor_y += self.row_height;
+        self.row_height = 0;
+        self.width = width;
+        self.height = height;
+        self.pixels = pixels;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(character: char) -> GlyphKey {
+        GlyphKey {
+            font: Font::default(),
+            character,
+            size: 16,
+        }
+    }
+
+    #[test]
+    fn glyphs_are_packed_in_rows() {
+        let mut atlas = GlyphAtlas::new(16, 16);
+        let a = atlas.insert(key('a'), 6, 4, &[0xFF; 24]).unwrap();
+        let b = atlas.insert(key('b'), 6, 5, &[0xFF; 30]).unwrap();
+        let c = atlas.insert(key('c'), 6, 3, &[0xFF; 18]).unwrap();
+
+        assert_eq!((a.x, a.y), (0, 0));
+        assert_eq!((b.x, b.y), (7, 0));
+        assert_eq!((c.x, c.y), (0, 6));
+        assert_eq!((atlas.width, atlas.height), (16, 16));
+    }
+
+    #[test]
+    fn growing_keeps_cached_glyphs_in_place() {
+        let mut atlas = GlyphAtlas::new(8, 8);
+        let a = atlas.insert(key('a'), 4, 4, &[0x80; 16]).unwrap();
+        let b = atlas.insert(key('b'), 6, 6, &[0xFF; 36]).unwrap();
+
+        assert_eq!((atlas.width, atlas.height), (16, 16));
+        assert_eq!(atlas.get(&key('a')), Some(a));
+        assert_eq!((b.x, b.y), (0, 5));
+        assert_eq!(atlas.pixels[3], 0x80);
+        assert_eq!(atlas.uv(&key('a')), Some(([0.0, 0.0], [0.25, 0.25])));
+    }
+
+    #[test]
+    fn full_atlas_is_cleared_at_its_maximum_size() {
+        let mut atlas = GlyphAtlas::new(8, 8);
+        atlas.max_size = 16;
+        atlas.insert(key('a'), 14, 14, &[0xFF; 196]).unwrap();
+        let b = atlas.insert(key('b'), 14, 14, &[0x80; 196]).unwrap();
+
+        assert_eq!((atlas.width, atlas.height), (16, 16));
+        assert_eq!((b.x, b.y), (0, 0));
+        assert_eq!(atlas.get(&key('a')), None);
+        assert_eq!(atlas.pixels[3], 0x80);
+        assert_eq!(atlas.insert(key('c'), 16, 1, &[0xFF; 16]), None);
+    }
+
+    #[test]
+    fn growth_stops_before_overflowing() {
+        let mut atlas = GlyphAtlas::new(32768, 1);
+        atlas.max_size = u16::MAX;
+        let wide = atlas.insert(key('w'), 32768, 1, &[0xFF; 32768]).unwrap();
+
+        assert_eq!((atlas.width, atlas.height), (u16::MAX, 2));
+        assert_eq!((wide.x, wide.y), (0, 0));
+        assert!(!atlas.can_hold(u16::MAX, 1));
+    }
+}
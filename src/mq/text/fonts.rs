@@ -0,0 +1,336 @@
+use std::{collections::HashMap, path::Path};
+
+use bevy_math::Vec2;
+
+use super::{
+    atlas::{GlyphAtlas, GlyphKey},
+    TextDimensions, TextParams,
+};
+
+const DEFAULT_FONT: &[u8] = include_bytes!("ProggyClean.ttf");
+
+/// Handle to a font stored in `Fonts`, `Font::default()` is the embedded font.
+///
+/// Unloading a font invalidates its handle, even if the slot is later reused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Font {
+    index: usize,
+    generation: u32,
+}
+
+#[derive(Debug)]
+pub enum FontError {
+    /// The bytes could not be parsed as a TTF or OTF font
+    Parse(&'static str),
+    Io(std::io::Error),
+    /// The handle was never returned by this `Fonts`
+    Unknown(Font),
+    /// The font behind the handle has been unloaded
+    Stale(Font),
+    /// The embedded font backs every `TextParams::default()` and stays loaded
+    DefaultFont,
+}
+
+impl From<&'static str> for FontError {
+    fn from(s: &'static str) -> Self {
+        Self::Parse(s)
+    }
+}
+
+impl From<std::io::Error> for FontError {
+    fn from(error: std::io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl std::fmt::Display for FontError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FontError::Parse(reason) => write!(f, "font error: {}", reason),
+            FontError::Io(error) => write!(f, "font error: {}", error),
+            FontError::Unknown(font) => write!(f, "font error: unknown handle {:?}", font),
+            FontError::Stale(font) => {
+                write!(
+                    f,
+                    "font error: handle {:?} points to an unloaded font",
+                    font
+                )
+            }
+            FontError::DefaultFont => write!(f, "font error: the default font can not be unloaded"),
+        }
+    }
+}
+
+impl std::error::Error for FontError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FontError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct GlyphMetrics {
+    pub offset_x: f32,
+    pub offset_y: f32,
+    pub width: u16,
+    pub height: u16,
+    pub advance: f32,
+}
+
+pub(crate) struct FontInternal {
+    font: fontdue::Font,
+    glyphs: HashMap<(char, u16), GlyphMetrics>,
+}
+
+impl FontInternal {
+    fn from_bytes(bytes: &[u8]) -> Result<Self, FontError> {
+        Ok(Self {
+            font: fontdue::Font::from_bytes(bytes, fontdue::FontSettings::default())?,
+            glyphs: HashMap::new(),
+        })
+    }
+
+    pub fn line_height(&self, font_size: u16) -> f32 {
+        self.font
+            .horizontal_line_metrics(font_size as f32)
+            .map(|metrics| metrics.new_line_size)
+            .unwrap_or(font_size as f32)
+    }
+
+    pub fn cache_glyph(&mut self, atlas: &mut GlyphAtlas, key: GlyphKey) -> GlyphMetrics {
+        // Glyphs evicted by a cleared atlas are rasterized again
+        if let Some(metrics) = self.glyphs.get(&(key.character, key.size)) {
+            let packed = metrics.width == 0
+                || metrics.height == 0
+                || !atlas.can_hold(metrics.width, metrics.height)
+                || atlas.get(&key).is_some();
+            if packed {
+                return *metrics;
+            }
+        }
+
+        let (metrics, coverage) = self.font.rasterize(key.character, key.size as f32);
+        let glyph = GlyphMetrics {
+            offset_x: metrics.xmin as f32,
+            offset_y: metrics.ymin as f32,
+            width: metrics.width as u16,
+            height: metrics.height as u16,
+            advance: metrics.advance_width,
+        };
+        if glyph.width > 0 && glyph.height > 0 {
+            atlas.insert(key, glyph.width, glyph.height, &coverage);
+        }
+        self.glyphs.insert((key.character, key.size), glyph);
+        glyph
+    }
+}
+
+struct FontSlot {
+    generation: u32,
+    font: Option<FontInternal>,
+}
+
+/// Every loaded font along with the glyph atlas they share
+pub struct Fonts {
+    slots: Vec<FontSlot>,
+    atlas: GlyphAtlas,
+}
+
+impl Default for Fonts {
+    fn default() -> Self {
+        let default_font =
+            FontInternal::from_bytes(DEFAULT_FONT).expect("The embedded font MUST be valid");
+        Self {
+            slots: vec![FontSlot {
+                generation: 0,
+                font: Some(default_font),
+            }],
+            atlas: GlyphAtlas::default(),
+        }
+    }
+}
+
+impl Fonts {
+    /// Loads a TTF or OTF font, reusing the slot of a previously unloaded font if any
+    pub fn load_from_bytes(&mut self, bytes: &[u8]) -> Result<Font, FontError> {
+        let font = FontInternal::from_bytes(bytes)?;
+        let free_slot = self.slots.iter().position(|slot| slot.font.is_none());
+        let index = match free_slot {
+            Some(index) => index,
+            None => {
+                self.slots.push(FontSlot {
+                    generation: 0,
+                    font: None,
+                });
+                self.slots.len() - 1
+            }
+        };
+        let slot = &mut self.slots[index];
+        slot.font = Some(font);
+        Ok(Font {
+            index,
+            generation: slot.generation,
+        })
+    }
+
+    pub fn load(&mut self, path: impl AsRef<Path>) -> Result<Font, FontError> {
+        let bytes = std::fs::read(path)?;
+        self.load_from_bytes(&bytes)
+    }
+
+    /// Frees the font, glyphs already in the atlas are kept until the atlas is rebuilt
+    pub fn unload(&mut self, font: Font) -> Result<(), FontError> {
+        if font == Font::default() {
+            return Err(FontError::DefaultFont);
+        }
+        self.get(font)?;
+        let slot = &mut self.slots[font.index];
+        slot.font = None;
+        slot.generation += 1;
+        Ok(())
+    }
+
+    /// Measures `text` with the glyph metrics used to draw it
+    pub fn measure_text(
+        &mut self,
+        text: &str,
+        params: &TextParams,
+    ) -> Result<TextDimensions, FontError> {
+        let mut bounds: Option<(f32, f32)> = None;
+        let width = self.layout(text, params, |_, min, max| {
+            let (top, bottom) = bounds.get_or_insert((min.y, max.y));
+            *top = top.min(min.y);
+            *bottom = bottom.max(max.y);
+        })?;
+
+        let (top, bottom) = bounds.unwrap_or_default();
+        Ok(TextDimensions {
+            width,
+            height: bottom - top,
+            offset_y: -top,
+        })
+    }
+
+    /// Lays `text` out with its first baseline starting at the origin, y pointing down.
+    ///
+    /// `glyph` is called with the bounds of every visible glyph, the widest line advance is returned.
+    pub(crate) fn layout(
+        &mut self,
+        text: &str,
+        params: &TextParams,
+        mut glyph: impl FnMut(GlyphKey, Vec2, Vec2),
+    ) -> Result<f32, FontError> {
+        let (font, atlas) = self.get_mut(params.font)?;
+        let scale = Vec2::new(
+            params.font_scale * params.font_scale_aspect,
+            params.font_scale,
+        );
+
+        let mut pen = Vec2::ZERO;
+        let mut width: f32 = 0.0;
+        for character in text.chars() {
+            if character == '\n' {
+                width = width.max(pen.x);
+                pen.x = 0.0;
+                pen.y += font.line_height(params.font_size) * scale.y;
+                continue;
+            }
+
+            let key = GlyphKey {
+                font: params.font,
+                character,
+                size: params.font_size,
+            };
+            let metrics = font.cache_glyph(atlas, key);
+            if metrics.width > 0 && metrics.height > 0 {
+                let size = Vec2::new(metrics.width as f32, metrics.height as f32);
+                let min = pen + Vec2::new(metrics.offset_x, -(size.y + metrics.offset_y)) * scale;
+                glyph(key, min, min + size * scale);
+            }
+            pen.x += metrics.advance * scale.x;
+        }
+        Ok(width.max(pen.x))
+    }
+
+    pub fn contains(&self, font: Font) -> bool {
+        self.get(font).is_ok()
+    }
+
+    fn get(&self, font: Font) -> Result<&FontInternal, FontError> {
+        let slot = self.slots.get(font.index).ok_or(FontError::Unknown(font))?;
+        if slot.generation != font.generation {
+            return Err(FontError::Stale(font));
+        }
+        slot.font.as_ref().ok_or(FontError::Stale(font))
+    }
+
+    /// The font behind `font` along with the atlas its glyphs are cached in
+    pub(crate) fn get_mut(
+        &mut self,
+        font: Font,
+    ) -> Result<(&mut FontInternal, &mut GlyphAtlas), FontError> {
+        self.get(font)?;
+        let font = self.slots[font.index]
+            .font
+            .as_mut()
+            .expect("The handle was just validated");
+        Ok((font, &mut self.atlas))
+    }
+
+    pub(crate) fn atlas(&self) -> &GlyphAtlas {
+        &self.atlas
+    }
+
+    pub(crate) fn atlas_mut(&mut self) -> &mut GlyphAtlas {
+        &mut self.atlas
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_font_is_loaded() {
+        let mut fonts = Fonts::default();
+        assert!(fonts.contains(Font::default()));
+        assert!(matches!(
+            fonts.unload(Font::default()),
+            Err(FontError::DefaultFont)
+        ));
+    }
+
+    #[test]
+    fn invalid_bytes_are_rejected() {
+        let mut fonts = Fonts::default();
+        assert!(matches!(
+            fonts.load_from_bytes(&[0, 1, 2, 3]),
+            Err(FontError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn unknown_handles_are_reported() {
+        let fonts = Fonts::default();
+        let font = Font {
+            index: 3,
+            generation: 0,
+        };
+        assert!(matches!(fonts.get(font), Err(FontError::Unknown(_))));
+    }
+
+    #[test]
+    fn unloaded_handles_go_stale() {
+        let mut fonts = Fonts::default();
+        let first = fonts.load_from_bytes(DEFAULT_FONT).unwrap();
+        fonts.unload(first).unwrap();
+        let second = fonts.load_from_bytes(DEFAULT_FONT).unwrap();
+
+        assert_eq!(first.index, second.index);
+        assert_ne!(first, second);
+        assert!(matches!(fonts.get(first), Err(FontError::Stale(_))));
+        assert!(fonts.contains(second));
+    }
+}
@@ -0,0 +1,236 @@
+mod atlas;
+mod fonts;
+
+use crate::prelude::*;
+
+use super::shaders::{text::MAX_GLYPHS, TextVertex};
+use atlas::GlyphKey;
+
+pub use fonts::{Font, FontError, Fonts};
+
+#[derive(Debug, Clone, Copy)]
+pub struct TextParams {
+    pub font: Font,
+    /// Size in pixels the glyphs are rasterized at
+    pub font_size: u16,
+    /// Glyphs are drawn at `font_size * font_scale`, far from 1.0 they get blurry
+    pub font_scale: f32,
+    /// Horizontal scale on top of `font_scale`
+    pub font_scale_aspect: f32,
+    pub color: Color,
+}
+
+impl Default for TextParams {
+    fn default() -> Self {
+        Self {
+            font: Font::default(),
+            font_size: 20,
+            font_scale: 1.0,
+            font_scale_aspect: 1.0,
+            color: Color::WHITE,
+        }
+    }
+}
+
+/// Size of a laid out text, in the same pixels `DebugText::draw` uses
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct TextDimensions {
+    /// Advance of the widest line
+    pub width: f32,
+    /// Distance from the top of the highest glyph to the bottom of the lowest one
+    pub height: f32,
+    /// Distance from the top of the text down to the first baseline, the text covers
+    /// `position.y - offset_y` to `position.y - offset_y + height` when drawn at `position`
+    pub offset_y: f32,
+}
+
+/// A glyph waiting for the `RenderStage`, uvs are resolved at flush time since the atlas may still grow
+#[derive(Debug, Clone, Copy)]
+struct QueuedGlyph {
+    key: GlyphKey,
+    min: Vec2,
+    max: Vec2,
+    color: Vec4,
+}
+
+/// Immediate mode text, drawn in screen space during the `RenderStage` and cleared afterwards.
+#[derive(Debug, Clone, Default)]
+pub struct DebugText {
+    queue: Vec<QueuedGlyph>,
+}
+
+impl DebugText {
+    /// Queues `text` with its baseline starting at `position`, in window pixels
+    pub fn draw(
+        &mut self,
+        fonts: &mut Fonts,
+        text: &str,
+        position: Vec2,
+        params: TextParams,
+    ) -> Result<(), FontError> {
+        let color: Vec4 = params.color.into();
+        let queue = &mut self.queue;
+        fonts.layout(text, &params, |key, min, max| {
+            queue.push(QueuedGlyph {
+                key,
+                min: position + min,
+                max: position + max,
+                color,
+            })
+        })?;
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.queue.clear();
+    }
+
+    /// Packs the queued glyphs again if a clear of the atlas evicted them after they were queued.
+    ///
+    /// Returns how many are still missing, once the glyphs of the frame do not fit the atlas
+    /// together.
+    pub(crate) fn cache_glyphs(&self, fonts: &mut Fonts) -> usize {
+        for glyph in &self.queue {
+            if fonts.atlas().get(&glyph.key).is_some() {
+                continue;
+            }
+            if let Ok((font, atlas)) = fonts.get_mut(glyph.key.font) {
+                font.cache_glyph(atlas, glyph.key);
+            }
+        }
+        self.queue
+            .iter()
+            .filter(|glyph| fonts.atlas().get(&glyph.key).is_none())
+            .count()
+    }
+
+    /// Quads for every queued glyph in the atlas, split so each chunk fits a single draw call
+    pub(crate) fn batches<'a>(
+        &'a self,
+        fonts: &'a Fonts,
+    ) -> impl Iterator<Item = (Vec<TextVertex>, Vec<u16>)> + 'a {
+        let atlas = fonts.atlas();
+        self.queue.chunks(MAX_GLYPHS).map(move |glyphs| {
+            let mut vertices = Vec::with_capacity(glyphs.len() * 4);
+            let mut indices = Vec::with_capacity(glyphs.len() * 6);
+            for glyph in glyphs {
+                let ([u0, v0], [u1, v1]) = match atlas.uv(&glyph.key) {
+                    Some(uv) => uv,
+                    None => continue,
+                };
+                let offset = vertices.len() as u16;
+                #[rustfmt::skip]
+                let quad = [
+                    TextVertex { position: Vec3::new(glyph.min.x, glyph.min.y, 0.0), uv: Vec2::new(u0, v0), color: glyph.color },
+                    TextVertex { position: Vec3::new(glyph.max.x, glyph.min.y, 0.0), uv: Vec2::new(u1, v0), color: glyph.color },
+                    TextVertex { position: Vec3::new(glyph.max.x, glyph.max.y, 0.0), uv: Vec2::new(u1, v1), color: glyph.color },
+                    TextVertex { position: Vec3::new(glyph.min.x, glyph.max.y, 0.0), uv: Vec2::new(u0, v1), color: glyph.color },
+                ];
+                vertices.extend_from_slice(&quad);
+                indices.extend([0, 1, 2, 0, 2, 3].iter().map(|index| index + offset));
+            }
+            (vertices, indices)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{atlas::GlyphAtlas, *};
+
+    #[test]
+    fn glyphs_are_queued_until_cleared() {
+        let mut fonts = Fonts::default();
+        let mut text = DebugText::default();
+        text.draw(&mut fonts, "a b", Vec2::ZERO, TextParams::default())
+            .unwrap();
+
+        let (vertices, indices) = text.batches(&fonts).next().unwrap();
+        assert_eq!(vertices.len(), 2 * 4);
+        assert_eq!(indices.len(), 2 * 6);
+
+        text.clear();
+        assert!(text.is_empty());
+    }
+
+    #[test]
+    fn evicted_glyphs_are_packed_again() {
+        let mut fonts = Fonts::default();
+        let mut text = DebugText::default();
+        text.draw(&mut fonts, "ab", Vec2::ZERO, TextParams::default())
+            .unwrap();
+        *fonts.atlas_mut() = GlyphAtlas::default();
+
+        assert_eq!(text.cache_glyphs(&mut fonts), 0);
+        let (vertices, _) = text.batches(&fonts).next().unwrap();
+        assert_eq!(vertices.len(), 2 * 4);
+    }
+
+    #[test]
+    fn stale_fonts_are_reported() {
+        let mut fonts = Fonts::default();
+        let mut text = DebugText::default();
+        let font = fonts
+            .load_from_bytes(include_bytes!("ProggyClean.ttf"))
+            .unwrap();
+        fonts.unload(font).unwrap();
+
+        let params = TextParams {
+            font,
+            ..Default::default()
+        };
+        let result = text.draw(&mut fonts, "score", Vec2::ZERO, params);
+        assert!(matches!(result, Err(FontError::Stale(_))));
+        assert!(text.is_empty());
+    }
+
+    #[test]
+    fn measurement_matches_drawn_glyphs() {
+        let mut fonts = Fonts::default();
+        let mut text = DebugText::default();
+        let params = TextParams {
+            font_scale: 2.0,
+            ..Default::default()
+        };
+        let position = Vec2::new(10.0, 50.0);
+        text.draw(&mut fonts, "Score: 42\nqg", position, params)
+            .unwrap();
+        let dimensions = fonts.measure_text("Score: 42\nqg", &params).unwrap();
+
+        let (vertices, _) = text.batches(&fonts).next().unwrap();
+        let top = vertices
+            .iter()
+            .map(|v| v.position.y)
+            .fold(f32::MAX, f32::min);
+        let bottom = vertices
+            .iter()
+            .map(|v| v.position.y)
+            .fold(f32::MIN, f32::max);
+        assert_eq!(top, position.y - dimensions.offset_y);
+        assert_eq!(bottom - top, dimensions.height);
+    }
+
+    #[test]
+    fn width_is_the_widest_line() {
+        let mut fonts = Fonts::default();
+        let params = TextParams::default();
+        let short = fonts.measure_text("ab", &params).unwrap();
+        let long = fonts.measure_text("abcd", &params).unwrap();
+        let both = fonts.measure_text("ab\nabcd", &params).unwrap();
+
+        assert!(long.width > short.width);
+        assert_eq!(both.width, long.width);
+        assert!(both.height > long.height);
+    }
+
+    #[test]
+    fn empty_text_has_no_size() {
+        let mut fonts = Fonts::default();
+        let dimensions = fonts.measure_text("", &TextParams::default()).unwrap();
+        assert_eq!(dimensions, TextDimensions::default());
+    }
+}
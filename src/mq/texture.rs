@@ -0,0 +1,186 @@
+use std::path::Path;
+
+use miniquad::{FilterMode, TextureFormat, TextureParams};
+
+/// Image file formats `Image` can decode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Png,
+    Qoi,
+    Tga,
+}
+
+impl ImageFormat {
+    /// Detects the format from the file signature, TGA has none and is never detected
+    pub fn from_signature(bytes: &[u8]) -> Option<Self> {
+        match image::guess_format(bytes).ok()? {
+            image::ImageFormat::Png => Some(Self::Png),
+            image::ImageFormat::Qoi => Some(Self::Qoi),
+            _ => None,
+        }
+    }
+
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_ascii_lowercase().as_str() {
+            "png" => Some(Self::Png),
+            "qoi" => Some(Self::Qoi),
+            "tga" => Some(Self::Tga),
+            _ => None,
+        }
+    }
+
+    fn decoder_format(self) -> image::ImageFormat {
+        match self {
+            ImageFormat::Png => image::ImageFormat::Png,
+            ImageFormat::Qoi => image::ImageFormat::Qoi,
+            ImageFormat::Tga => image::ImageFormat::Tga,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum TextureError {
+    /// The format could not be detected or is not one of `ImageFormat`
+    UnsupportedFormat,
+    Decode(image::ImageError),
+    Io(std::io::Error),
+}
+
+impl From<image::ImageError> for TextureError {
+    fn from(error: image::ImageError) -> Self {
+        match error {
+            image::ImageError::Unsupported(_) => Self::UnsupportedFormat,
+            error => Self::Decode(error),
+        }
+    }
+}
+
+impl From<std::io::Error> for TextureError {
+    fn from(error: std::io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl std::fmt::Display for TextureError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TextureError::UnsupportedFormat => write!(f, "texture error: unsupported image format"),
+            TextureError::Decode(error) => write!(f, "texture error: {}", error),
+            TextureError::Io(error) => write!(f, "texture error: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for TextureError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TextureError::Decode(error) => Some(error),
+            TextureError::Io(error) => Some(error),
+            TextureError::UnsupportedFormat => None,
+        }
+    }
+}
+
+/// Decoded pixels, RGB8 for opaque images and RGBA8 otherwise
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub format: TextureFormat,
+    pub bytes: Vec<u8>,
+}
+
+impl Image {
+    /// Decodes an image file, the format is detected from its signature when `None`
+    pub fn from_file_bytes(
+        bytes: &[u8],
+        format: Option<ImageFormat>,
+    ) -> Result<Self, TextureError> {
+        let format = format
+            .or_else(|| ImageFormat::from_signature(bytes))
+            .ok_or(TextureError::UnsupportedFormat)?;
+        let decoded = image::load_from_memory_with_format(bytes, format.decoder_format())?;
+
+        let (width, height) = (decoded.width(), decoded.height());
+        let (format, bytes) = if decoded.color().has_alpha() {
+            (TextureFormat::RGBA8, decoded.into_rgba8().into_raw())
+        } else {
+            (TextureFormat::RGB8, decoded.into_rgb8().into_raw())
+        };
+        Ok(Self {
+            width,
+            height,
+            format,
+            bytes,
+        })
+    }
+
+    /// Reads and decodes an image file, the format is detected from the extension or the signature
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, TextureError> {
+        let path = path.as_ref();
+        let format = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .and_then(ImageFormat::from_extension);
+        let bytes = std::fs::read(path)?;
+        Self::from_file_bytes(&bytes, format)
+    }
+
+    pub fn texture_params(&self, filter: FilterMode) -> TextureParams {
+        TextureParams {
+            format: self.format,
+            width: self.width,
+            height: self.height,
+            filter,
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn png_is_detected_and_decoded() {
+        let pixels = [0xFF, 0x00, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0x80];
+        let mut file = Vec::new();
+        image::write_buffer_with_format(
+            &mut std::io::Cursor::new(&mut file),
+            &pixels,
+            2,
+            1,
+            image::ColorType::Rgba8,
+            image::ImageOutputFormat::Png,
+        )
+        .unwrap();
+
+        let decoded = Image::from_file_bytes(&file, None).unwrap();
+        assert_eq!((decoded.width, decoded.height), (2, 1));
+        assert_eq!(decoded.format, TextureFormat::RGBA8);
+        assert_eq!(decoded.bytes, pixels);
+    }
+
+    #[test]
+    fn opaque_tga_decodes_to_rgb() {
+        // Uncompressed true color, 1x1, 24 bits per pixel, stored as BGR
+        let mut file = vec![0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 1, 0, 24, 0];
+        file.extend_from_slice(&[0x30, 0x20, 0x10]);
+
+        let decoded = Image::from_file_bytes(&file, Some(ImageFormat::Tga)).unwrap();
+        assert_eq!(decoded.format, TextureFormat::RGB8);
+        assert_eq!(decoded.bytes, vec![0x10, 0x20, 0x30]);
+    }
+
+    #[test]
+    fn unknown_formats_are_rejected() {
+        assert!(matches!(
+            Image::from_file_bytes(b"GIF89a", None),
+            Err(TextureError::UnsupportedFormat)
+        ));
+        assert!(matches!(
+            Image::from_file_bytes(&[0x89, b'P', b'N', b'G'], Some(ImageFormat::Png)),
+            Err(TextureError::Decode(_))
+        ));
+    }
+}
@@ -0,0 +1,138 @@
+use image::{
+    error::{ParameterError, ParameterErrorKind},
+    imageops::FilterType,
+    ImageError, RgbaImage,
+};
+use miniquad::{
+    conf::{Conf, Icon},
+    TextureFormat,
+};
+
+use super::{Image, TextureError};
+
+/// Window created by the `MiniquadPlugin` runner, insert it before `App::run`
+#[derive(Debug, Clone)]
+pub struct WindowDescriptor {
+    pub title: String,
+    /// Preferred size in pixels, ignored on wasm and android
+    pub width: u32,
+    pub height: u32,
+    /// Ignored on wasm and android
+    pub fullscreen: bool,
+    /// Renders at full resolution on high DPI displays
+    pub high_dpi: bool,
+    pub resizable: bool,
+    /// MSAA samples per pixel
+    pub sample_count: u32,
+    /// Scaled down to the 16, 32 and 64 pixels large icons of the taskbar and title bar
+    pub icon: Option<Image>,
+}
+
+impl Default for WindowDescriptor {
+    fn default() -> Self {
+        Self {
+            title: "bevyquad".to_string(),
+            width: 1024,
+            height: 768,
+            fullscreen: false,
+            high_dpi: false,
+            resizable: true,
+            sample_count: 1,
+            icon: None,
+        }
+    }
+}
+
+impl WindowDescriptor {
+    /// Leaves out an icon that `icon` fails to convert
+    pub fn conf(&self) -> Conf {
+        Conf {
+            window_title: self.title.clone(),
+            window_width: self.width as i32,
+            window_height: self.height as i32,
+            high_dpi: self.high_dpi,
+            fullscreen: self.fullscreen,
+            sample_count: self.sample_count.max(1) as i32,
+            window_resizable: self.resizable,
+            icon: self.icon().ok().flatten(),
+        }
+    }
+
+    /// The window icon made from `icon`, which has to be an RGB8 or RGBA8 image
+    pub fn icon(&self) -> Result<Option<Icon>, TextureError> {
+        self.icon.as_ref().map(icon).transpose()
+    }
+}
+
+fn icon(image: &Image) -> Result<Icon, TextureError> {
+    let rgba = match image.format {
+        TextureFormat::RGBA8 => image.bytes.clone(),
+        TextureFormat::RGB8 => image
+            .bytes
+            .chunks_exact(3)
+            .flat_map(|rgb| [rgb[0], rgb[1], rgb[2], 0xFF])
+            .collect(),
+        _ => return Err(TextureError::UnsupportedFormat),
+    };
+    let image = RgbaImage::from_raw(image.width, image.height, rgba).ok_or_else(|| {
+        ImageError::Parameter(ParameterError::from_kind(
+            ParameterErrorKind::DimensionMismatch,
+        ))
+    })?;
+    let mut icon = Icon {
+        small: [0; 16 * 16 * 4],
+        medium: [0; 32 * 32 * 4],
+        big: [0; 64 * 64 * 4],
+    };
+    for (size, pixels) in [
+        (16, &mut icon.small[..]),
+        (32, &mut icon.medium[..]),
+        (64, &mut icon.big[..]),
+    ] {
+        let resized = image::imageops::resize(&image, size, size, FilterType::Triangle);
+        pixels.copy_from_slice(resized.as_raw());
+    }
+    Ok(icon)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn descriptor_is_converted_to_conf() {
+        let descriptor = WindowDescriptor {
+            title: "Chess".to_string(),
+            width: 640,
+            height: 480,
+            sample_count: 4,
+            icon: Some(Image {
+                width: 2,
+                height: 2,
+                format: TextureFormat::RGB8,
+                bytes: vec![0x40; 2 * 2 * 3],
+            }),
+            ..Default::default()
+        };
+        let conf = descriptor.conf();
+        assert_eq!(conf.window_title, "Chess");
+        assert_eq!((conf.window_width, conf.window_height), (640, 480));
+        assert_eq!(conf.sample_count, 4);
+        assert!(conf.window_resizable);
+
+        let icon = conf.icon.unwrap();
+        assert_eq!(icon.small[..4], [0x40, 0x40, 0x40, 0xFF]);
+        assert_eq!(icon.big[64 * 64 * 4 - 4..], [0x40, 0x40, 0x40, 0xFF]);
+    }
+
+    #[test]
+    fn icons_need_color_images() {
+        let image = Image {
+            width: 1,
+            height: 1,
+            format: TextureFormat::Alpha,
+            bytes: vec![0xFF],
+        };
+        assert!(matches!(icon(&image), Err(TextureError::UnsupportedFormat)));
+    }
+}